@@ -0,0 +1,169 @@
+// Per-profile user-level Claude Code assets (CLAUDE.md, commands/, agents/),
+// kept under cswitch's data dir and symlinked into Claude Code's config dir
+// on `use`. The user's own copies are stashed while a profile's are linked
+// and put back when switching to a profile without them.
+
+use colored::Colorize;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CswitchError, Result};
+use crate::profile;
+
+/// The assets a profile can own, as named in Claude Code's config dir.
+pub const ASSETS: &[&str] = &["CLAUDE.md", "commands", "agents"];
+
+fn data_root() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| CswitchError::Config("Cannot determine data directory".into()))?;
+    Ok(data_dir.join("cswitch"))
+}
+
+fn assets_root() -> Result<PathBuf> {
+    Ok(data_root()?.join("assets"))
+}
+
+/// `<data dir>/cswitch/assets/<name>`, holding the profile's assets.
+pub fn profile_dir(profile_name: &str) -> Result<PathBuf> {
+    profile::validate_name(profile_name)?;
+    Ok(assets_root()?.join(profile_name))
+}
+
+/// Delete `profile_name`'s assets, unless the directory turns out (through
+/// a symlink, say) to be somewhere other than under the assets root.
+pub fn remove_profile_dir(profile_name: &str) -> Result<()> {
+    let dir = profile_dir(profile_name)?;
+    let (Ok(real), Ok(root)) = (fs::canonicalize(&dir), fs::canonicalize(assets_root()?)) else {
+        return Ok(());
+    };
+    if real.parent() != Some(root.as_path()) {
+        warn(format!("Not removing {}: it resolves outside {}", dir.display(), root.display()));
+        return Ok(());
+    }
+    fs::remove_dir_all(real)?;
+    Ok(())
+}
+
+/// Where the user's own assets wait while a profile's are linked.
+fn stash_dir() -> Result<PathBuf> {
+    Ok(data_root()?.join("assets-stash"))
+}
+
+/// The assets `profile_name` has.
+pub fn profile_assets(profile_name: &str) -> Result<Vec<&'static str>> {
+    let dir = profile_dir(profile_name)?;
+    Ok(ASSETS.iter().copied().filter(|name| dir.join(name).exists()).collect())
+}
+
+/// Whether `path` exists, without following a symlink.
+fn present(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// The profile whose asset `live` is a cswitch symlink to, if it is one.
+pub fn linked_profile(live: &Path) -> Option<String> {
+    let target = fs::read_link(live).ok()?;
+    let rel = target.strip_prefix(assets_root().ok()?).ok()?;
+    rel.components().next()?.as_os_str().to_str().map(str::to_string)
+}
+
+fn warn(message: String) {
+    eprintln!("{} {message}", "Warning:".yellow().bold());
+}
+
+/// Link `profile_name`'s assets into `claude_dir` in place of the previous
+/// profile's, and return the names now linked. `linked` is what the last
+/// call returned. A live asset that replaced one of our links since (an
+/// edit saved as a new file, say) is a conflict: it is reported and left
+/// alone, never overwritten.
+pub fn apply(claude_dir: &Path, linked: &[String], profile_name: Option<&str>) -> Result<Vec<String>> {
+    let wanted = match profile_name {
+        Some(name) => profile_assets(name)?,
+        None => Vec::new(),
+    };
+    let stash_dir = stash_dir()?;
+
+    let mut now_linked = Vec::new();
+    for &name in ASSETS {
+        let live = claude_dir.join(name);
+        let stash = stash_dir.join(name);
+        let ours = linked_profile(&live).is_some();
+
+        if linked.iter().any(|l| l == name) && !ours && present(&live) {
+            warn(format!(
+                "{} was replaced since cswitch linked it; leaving it alone. Save it with 'cswitch assets capture <profile> --only {name}' or move it away, then switch again.",
+                live.display()
+            ));
+            continue;
+        }
+
+        if wanted.contains(&name) {
+            if ours {
+                fs::remove_file(&live)?;
+            } else if present(&live) {
+                if present(&stash) {
+                    warn(format!(
+                        "Cannot set aside {}: {} already holds an earlier copy. Keeping the live one.",
+                        live.display(),
+                        stash.display()
+                    ));
+                    continue;
+                }
+                fs::create_dir_all(&stash_dir)?;
+                fs::rename(&live, &stash)?;
+            }
+            fs::create_dir_all(claude_dir)?;
+            symlink(profile_dir(profile_name.unwrap_or_default())?.join(name), &live)?;
+            now_linked.push(name.to_string());
+        } else {
+            if ours {
+                fs::remove_file(&live)?;
+            }
+            if !present(&live) && present(&stash) {
+                fs::rename(&stash, &live)?;
+            }
+        }
+    }
+    Ok(now_linked)
+}
+
+/// Link a profile's assets into its isolated config directory. Files the
+/// user put there themselves are left alone.
+pub fn link_into(dir: &Path, profile_name: &str) -> Result<()> {
+    let wanted = profile_assets(profile_name)?;
+    for &name in ASSETS {
+        let live = dir.join(name);
+        if linked_profile(&live).is_some() {
+            fs::remove_file(&live)?;
+        }
+        if wanted.contains(&name) && !present(&live) {
+            symlink(profile_dir(profile_name)?.join(name), &live)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy `from` to `to`, recursing into directories and following symlinks.
+pub fn copy_all(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Remove `path`, whether a file, a symlink or a directory tree.
+pub fn remove(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
@@ -1,70 +1,291 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::isolated;
+use crate::settings_overlay::{self, OverlayEntry};
 
-const API_KEY_HELPER_CMD: &str = "cswitch emit-key";
+pub const API_KEY_HELPER_CMD: &str = "cswitch emit-key";
 
-/// Return the path to ~/.claude/settings.json
-pub fn settings_path() -> Result<PathBuf> {
+/// Return Claude Code's config directory: `$CLAUDE_CONFIG_DIR`, or ~/.claude.
+/// A `CLAUDE_CONFIG_DIR` pointing at an isolated profile's directory is
+/// ignored: cswitch manages those itself, and the global login lives in ~/.claude.
+pub fn claude_dir() -> Result<PathBuf> {
+    if let Some(dir) = configured_dir() {
+        return Ok(dir);
+    }
     let home = dirs::home_dir()
         .ok_or_else(|| CswitchError::ClaudeSettings("Cannot determine home directory".into()))?;
-    Ok(home.join(".claude").join("settings.json"))
+    Ok(home.join(".claude"))
+}
+
+fn configured_dir() -> Option<PathBuf> {
+    std::env::var_os("CLAUDE_CONFIG_DIR")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .filter(|d| isolated::profile_for_dir(d).is_none())
+}
+
+/// Return the path to ~/.claude.json, Claude Code's state file (onboarding,
+/// project trust, the logged-in account). It moves into `$CLAUDE_CONFIG_DIR`
+/// when that is set.
+pub fn claude_json_path() -> Result<PathBuf> {
+    if let Some(dir) = configured_dir() {
+        return Ok(dir.join(CLAUDE_JSON_FILE));
+    }
+    let home = dirs::home_dir()
+        .ok_or_else(|| CswitchError::ClaudeSettings("Cannot determine home directory".into()))?;
+    Ok(home.join(CLAUDE_JSON_FILE))
+}
+
+pub const CLAUDE_JSON_FILE: &str = ".claude.json";
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Return the path to ~/.claude/settings.json
+pub fn settings_path() -> Result<PathBuf> {
+    Ok(claude_dir()?.join(SETTINGS_FILE))
 }
 
 /// Read settings.json as a serde_json::Value, or return an empty object if it doesn't exist.
 pub fn read_settings() -> Result<Value> {
-    let path = settings_path()?;
+    read_settings_at(&settings_path()?)
+}
+
+fn read_settings_at(path: &Path) -> Result<Value> {
     if !path.exists() {
         return Ok(serde_json::json!({}));
     }
-    let data = fs::read_to_string(&path)
+    let data = fs::read_to_string(path)
         .map_err(|e| CswitchError::ClaudeSettings(format!("read error: {e}")))?;
     let val: Value = serde_json::from_str(&data)
         .map_err(|e| CswitchError::ClaudeSettings(format!("parse error: {e}")))?;
     Ok(val)
 }
 
-/// Write settings.json, creating the directory if needed.
-fn write_settings(val: &Value) -> Result<()> {
-    let path = settings_path()?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+/// Write a settings file, creating the directory if needed.
+fn write_settings_at(path: &Path, val: &Value) -> Result<()> {
     let data = serde_json::to_string_pretty(val)
         .map_err(|e| CswitchError::ClaudeSettings(format!("serialize error: {e}")))?;
-    fs::write(&path, data)
+    fsutil::write_atomic(path, data.as_bytes(), 0o644)
         .map_err(|e| CswitchError::ClaudeSettings(format!("write error: {e}")))?;
     Ok(())
 }
 
-/// Set `apiKeyHelper` in settings.json to point to `cswitch emit-key`.
-pub fn enable_api_key_helper() -> Result<()> {
-    let mut settings = read_settings()?;
+/// Apply `f` to the settings.json object under cswitch's lock and write it back.
+fn update_settings(f: impl FnOnce(&mut serde_json::Map<String, Value>)) -> Result<()> {
+    update_settings_at(&settings_path()?, f)
+}
+
+fn update_settings_at(path: &Path, f: impl FnOnce(&mut serde_json::Map<String, Value>)) -> Result<()> {
+    let _lock = fsutil::lock(path)
+        .map_err(|e| CswitchError::ClaudeSettings(format!("lock error: {e}")))?;
+    let mut settings = read_settings_at(path)?;
     let obj = settings
         .as_object_mut()
         .ok_or_else(|| CswitchError::ClaudeSettings("settings.json is not an object".into()))?;
-    obj.insert(
-        "apiKeyHelper".to_string(),
-        Value::String(API_KEY_HELPER_CMD.to_string()),
-    );
-    write_settings(&settings)
+    f(obj);
+    write_settings_at(path, &settings)
+}
+
+/// Set `apiKeyHelper` in settings.json to point to `cswitch emit-key`.
+pub fn enable_api_key_helper() -> Result<()> {
+    update_settings(|obj| {
+        obj.insert(
+            "apiKeyHelper".to_string(),
+            Value::String(API_KEY_HELPER_CMD.to_string()),
+        );
+    })
 }
 
 /// Remove `apiKeyHelper` from settings.json.
 pub fn disable_api_key_helper() -> Result<()> {
+    update_settings(|obj| {
+        obj.shift_remove("apiKeyHelper");
+    })
+}
+
+/// Copy the global settings.json into an isolated config `dir` that has none,
+/// minus what cswitch put there for the active profile: `apiKeyHelper`, the
+/// env in `managed` and the overlay in `applied`.
+pub fn seed_settings(dir: &Path, managed: &[EnvEntry], applied: &[OverlayEntry]) -> Result<()> {
+    let path = dir.join(SETTINGS_FILE);
+    if path.exists() {
+        return Ok(());
+    }
     let mut settings = read_settings()?;
-    let obj = settings
-        .as_object_mut()
-        .ok_or_else(|| CswitchError::ClaudeSettings("settings.json is not an object".into()))?;
-    obj.remove("apiKeyHelper");
-    write_settings(&settings)
+    if let Some(obj) = settings.as_object_mut() {
+        if obj.get("apiKeyHelper").and_then(Value::as_str) == Some(API_KEY_HELPER_CMD) {
+            obj.shift_remove("apiKeyHelper");
+        }
+        merge_env(obj, managed, &BTreeMap::new());
+        settings_overlay::revert(obj, applied);
+    }
+    write_settings_at(&path, &settings)
+}
+
+/// One variable cswitch set in the `env` of a settings.json, and the value
+/// it replaced there, put back when the profile is switched away from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvEntry {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Value>,
+}
+
+/// What `use` changes in a settings.json: cswitch's `apiKeyHelper`, the
+/// profile's env and its settings overlay, each replacing the previous
+/// profile's. Keys the user set themselves are left alone, or put back.
+pub struct SettingsChange<'a> {
+    pub helper: bool,
+    /// Env the previous profile set
+    pub remove_env: &'a [EnvEntry],
+    pub set_env: &'a BTreeMap<String, String>,
+    /// The previous profile's overlay, as written
+    pub revert: &'a [OverlayEntry],
+    pub overlay: &'a serde_json::Map<String, Value>,
+}
+
+/// What a [`SettingsChange`] wrote, to be undone by the next one.
+#[derive(Default)]
+pub struct Applied {
+    pub env: Vec<EnvEntry>,
+    pub settings: Vec<OverlayEntry>,
+}
+
+impl SettingsChange<'_> {
+    /// Apply to a settings object and return what was written.
+    pub fn apply_to(&self, obj: &mut serde_json::Map<String, Value>) -> Applied {
+        settings_overlay::revert(obj, self.revert);
+        let env = merge_env(obj, self.remove_env, self.set_env);
+        if self.helper {
+            obj.insert("apiKeyHelper".to_string(), Value::String(API_KEY_HELPER_CMD.to_string()));
+        } else {
+            obj.shift_remove("apiKeyHelper");
+        }
+        Applied {
+            env,
+            settings: settings_overlay::merge(obj, self.overlay),
+        }
+    }
+}
+
+/// Apply `change` to ~/.claude/settings.json, or to the settings.json in an
+/// isolated config `dir`, in one locked write.
+pub fn apply_change(dir: Option<&Path>, change: &SettingsChange) -> Result<Applied> {
+    let path = match dir {
+        Some(dir) => dir.join(SETTINGS_FILE),
+        None => settings_path()?,
+    };
+    let mut applied = Applied::default();
+    update_settings_at(&path, |obj| applied = change.apply_to(obj))?;
+    Ok(applied)
+}
+
+/// Read the settings.json in `dir`, or ~/.claude/settings.json.
+pub fn read_settings_in(dir: Option<&Path>) -> Result<Value> {
+    match dir {
+        Some(dir) => read_settings_at(&dir.join(SETTINGS_FILE)),
+        None => read_settings(),
+    }
+}
+
+/// Take out the variables in `remove`, putting back the values they
+/// replaced, then set `set` and return what it replaced.
+fn merge_env(
+    obj: &mut serde_json::Map<String, Value>,
+    remove: &[EnvEntry],
+    set: &BTreeMap<String, String>,
+) -> Vec<EnvEntry> {
+    if !matches!(obj.get("env"), Some(Value::Object(_))) {
+        obj.insert("env".to_string(), Value::Object(serde_json::Map::new()));
+    }
+    let Some(Value::Object(env)) = obj.get_mut("env") else {
+        return Vec::new();
+    };
+    for entry in remove.iter().rev() {
+        match &entry.previous {
+            Some(previous) => env.insert(entry.key.clone(), previous.clone()),
+            None => env.shift_remove(&entry.key),
+        };
+    }
+    let written = set
+        .iter()
+        .map(|(key, value)| EnvEntry {
+            key: key.clone(),
+            previous: env.insert(key.clone(), Value::String(value.clone())),
+        })
+        .collect();
+    if env.is_empty() {
+        obj.shift_remove("env");
+    }
+    written
+}
+
+/// Read the `oauthAccount` section of ~/.claude.json: the account Claude
+/// Code shows as logged in.
+pub fn read_oauth_account() -> Result<Option<Value>> {
+    let path = claude_json_path()?;
+    let Ok(data) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let state: Value = serde_json::from_str(&data)
+        .map_err(|e| CswitchError::ClaudeSettings(format!("{}: parse error: {e}", path.display())))?;
+    Ok(state.get("oauthAccount").cloned())
+}
+
+/// Set `oauthAccount` in ~/.claude.json, or in the `.claude.json` of an
+/// isolated config `dir`. Claude Code rewrites this file as it runs, and it
+/// holds much more than the account (project history, trust, onboarding),
+/// so only that key is replaced, and the write is retried if the file
+/// changes between reading and writing it.
+pub fn write_oauth_account(dir: Option<&Path>, account: &Value) -> Result<()> {
+    let path = match dir {
+        Some(dir) => dir.join(CLAUDE_JSON_FILE),
+        None => claude_json_path()?,
+    };
+    let _lock = fsutil::lock(&path)
+        .map_err(|e| CswitchError::ClaudeSettings(format!("lock error: {e}")))?;
+
+    for _ in 0..3 {
+        let before = fs::read(&path).ok();
+        let mut state: Value = match &before {
+            Some(data) => serde_json::from_slice(data).map_err(|e| {
+                CswitchError::ClaudeSettings(format!("{}: parse error: {e}", path.display()))
+            })?,
+            None => serde_json::json!({}),
+        };
+        let obj = state.as_object_mut().ok_or_else(|| {
+            CswitchError::ClaudeSettings(format!("{} is not an object", path.display()))
+        })?;
+        if obj.get("oauthAccount") == Some(account) {
+            return Ok(());
+        }
+        obj.insert("oauthAccount".to_string(), account.clone());
+        let data = serde_json::to_string_pretty(&state)
+            .map_err(|e| CswitchError::ClaudeSettings(format!("serialize error: {e}")))?;
+
+        if fs::read(&path).ok() != before {
+            continue;
+        }
+        fsutil::write_atomic(&path, data.as_bytes(), 0o600)
+            .map_err(|e| CswitchError::ClaudeSettings(format!("write error: {e}")))?;
+        return Ok(());
+    }
+    Err(CswitchError::ClaudeSettings(format!(
+        "{} kept changing while cswitch was updating it; try again once Claude Code is idle",
+        path.display()
+    )))
 }
 
-/// Check if `apiKeyHelper` is currently set.
-#[allow(dead_code)]
-pub fn has_api_key_helper() -> Result<bool> {
+/// Return the current `apiKeyHelper` command, if any.
+pub fn api_key_helper() -> Result<Option<String>> {
     let settings = read_settings()?;
-    Ok(settings.get("apiKeyHelper").is_some())
+    Ok(settings
+        .get("apiKeyHelper")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string()))
 }
@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::secrets::BackendKind;
 
 #[derive(Parser)]
 #[command(name = "cswitch", version, about = "Switch between Anthropic/Claude accounts")]
@@ -13,6 +15,10 @@ pub enum Commands {
     Add {
         /// Profile name (prompted if omitted)
         name: Option<String>,
+
+        /// Add a `claude setup-token` profile; the token is read from stdin when piped
+        #[arg(long)]
+        setup_token: bool,
     },
 
     /// Switch to a profile (interactive selector)
@@ -21,12 +27,65 @@ pub enum Commands {
         name: Option<String>,
     },
 
+    /// Run a command as a profile, without switching globally
+    Exec {
+        /// Profile name
+        profile: String,
+
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Print shell exports for a profile (eval "$(cswitch env work)")
+    Env {
+        /// Profile name (defaults to the pinned or active profile)
+        profile: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = EnvShell::Bash)]
+        shell: EnvShell,
+
+        /// Print commands that clear the variables instead
+        #[arg(long)]
+        unset: bool,
+    },
+
+    /// Set variables in a profile's env block (applied to settings.json on use)
+    SetEnv {
+        /// Profile name
+        name: String,
+
+        /// KEY=VALUE pairs
+        #[arg(required = true)]
+        vars: Vec<String>,
+    },
+
+    /// Remove variables from a profile's env block
+    UnsetEnv {
+        /// Profile name
+        name: String,
+
+        /// Variable names
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+
     /// List all profiles
     List,
 
     /// Show the current active profile
     Current,
 
+    /// Print the profile that applies in this directory (pin or active)
+    Resolve,
+
+    /// Pin a profile to the current directory (writes .cswitch)
+    Pin {
+        /// Profile name
+        name: String,
+    },
+
     /// Remove a profile
     Remove {
         /// Profile name (prompted if omitted)
@@ -39,13 +98,136 @@ pub enum Commands {
         name: Option<String>,
     },
 
+    /// Make the active profile match the account Claude Code is logged in as
+    Reconcile,
+
     /// Initialize cswitch (create config directory)
     Init,
 
+    /// Print a shell snippet that switches profiles on `cd`
+    Hook {
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+
+    /// [hidden] Switch to the pinned or rule-matched profile, if it differs
+    #[command(hide = true)]
+    HookSwitch {
+        /// Also print shell code pointing CLAUDE_CONFIG_DIR at an isolated profile
+        #[arg(long, value_enum)]
+        shell: Option<HookShell>,
+
+        /// Only print the shell code; don't switch
+        #[arg(long)]
+        no_switch: bool,
+    },
+
+    /// Manage per-profile Claude Code settings (model, permissions, hooks)
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand,
+    },
+
+    /// Manage per-profile CLAUDE.md, commands and agents
+    Assets {
+        #[command(subcommand)]
+        command: AssetsCommand,
+    },
+
+    /// Give a profile its own Claude Code config directory
+    Isolate {
+        /// Profile name
+        name: String,
+
+        /// Go back to the global Claude Code config
+        #[arg(long)]
+        off: bool,
+    },
+
+    /// Diagnose why switching isn't working
+    Doctor {
+        /// Apply safe repairs (apiKeyHelper, file permissions)
+        #[arg(long)]
+        fix: bool,
+    },
+
     /// Update cswitch to the latest version
     Update,
 
-    /// [hidden] Emit the active API key for apiKeyHelper
+    /// Manage where profile secrets are stored
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+
+    /// [hidden] Emit the active API key or gateway token for apiKeyHelper
     #[command(hide = true)]
     EmitKey,
 }
+
+#[derive(Subcommand)]
+pub enum AssetsCommand {
+    /// Copy the live CLAUDE.md, commands/ and agents/ into a profile
+    Capture {
+        /// Profile name
+        profile: String,
+
+        /// Asset to capture (repeatable): CLAUDE.md, commands or agents
+        #[arg(long)]
+        only: Vec<String>,
+    },
+
+    /// Show the live assets and the active profile's set
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum SettingsCommand {
+    /// Save keys of the current settings.json as the profile's overlay
+    Capture {
+        /// Profile name
+        profile: String,
+
+        /// Top-level key to capture (repeatable; default: model, permissions, hooks)
+        #[arg(long = "key")]
+        keys: Vec<String>,
+    },
+
+    /// Show what switching to a profile would change in settings.json
+    Diff {
+        /// Profile name
+        profile: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SecretsCommand {
+    /// Show the configured secret backend
+    Status,
+
+    /// Move every profile's secrets to another backend
+    Migrate {
+        /// Backend to move secrets to
+        #[arg(long, value_enum)]
+        to: BackendKind,
+    },
+
+    /// Change the passphrase of the encrypted-file backend
+    Passphrase,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EnvShell {
+    Bash,
+    Fish,
+    Powershell,
+    Dotenv,
+    Json,
+}
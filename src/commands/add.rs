@@ -1,13 +1,22 @@
-use chrono::Utc;
+use chrono::{Duration, NaiveDate, Utc};
 use colored::Colorize;
 use dialoguer::{Confirm, Input, Password, Select};
+use std::io::{IsTerminal, Read};
 
+use crate::claude_config;
 use crate::error::{CswitchError, Result};
 use crate::keychain;
-use crate::profile::{Profile, ProfileStore, ProfileType};
+use crate::profile::{self, Account, Profile, ProfileStore, ProfileType, SETUP_TOKEN_LIFETIME_DAYS};
+use crate::secrets::reference;
 
+const SETUP_TOKEN_OPTION: &str = "Long-lived token (claude setup-token)";
+const CLAUDE_LOGIN_OPTION: &str = "OAuth via 'claude auth login' (replaces Claude Code's login)";
+
+pub fn run(name: Option<String>, setup_token: bool) -> Result<()> {
+    if setup_token && !std::io::stdin().is_terminal() {
+        return add_piped_setup_token(name);
+    }
 
-pub fn run(name: Option<String>) -> Result<()> {
     let mut store = ProfileStore::load()?;
 
     // 1. Ask for name
@@ -18,6 +27,7 @@ pub fn run(name: Option<String>) -> Result<()> {
             .interact_text()
             .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?,
     };
+    profile::validate_name(&name)?;
 
     let updating = store.profiles.contains_key(&name);
     if updating {
@@ -37,25 +47,31 @@ pub fn run(name: Option<String>) -> Result<()> {
         "API Key",
         "OAuth (login via browser)",
         "Import from Claude Code (existing login)",
+        CLAUDE_LOGIN_OPTION,
+        "Amazon Bedrock",
+        "Google Vertex AI",
+        "Gateway (bearer token)",
+        SETUP_TOKEN_OPTION,
     ];
 
-    let auth_choice = Select::new()
-        .with_prompt("Authentication type")
-        .items(&auth_options)
-        .default(0)
-        .interact()
-        .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
-
-    let selected = auth_options[auth_choice];
+    let selected = if setup_token {
+        SETUP_TOKEN_OPTION
+    } else {
+        let auth_choice = Select::new()
+            .with_prompt("Authentication type")
+            .items(&auth_options)
+            .default(0)
+            .interact()
+            .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+        auth_options[auth_choice]
+    };
 
     // 3. Get credentials
+    let mut oauth_account = None;
     let profile_type = if selected == "API Key" {
-        let key = Password::new()
-            .with_prompt("Anthropic API key")
-            .interact()
-            .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+        let key = prompt_secret("Anthropic API key (or env:VAR, file:PATH, cmd:COMMAND)")?;
 
-        if !key.starts_with("sk-ant-") {
+        if !reference::is_reference(&key) && !key.starts_with("sk-ant-") {
             eprintln!(
                 "{} Key doesn't start with 'sk-ant-'. Storing anyway.",
                 "Warning:".yellow().bold()
@@ -67,22 +83,55 @@ pub fn run(name: Option<String>) -> Result<()> {
     } else if selected.starts_with("Import") {
         let creds = keychain::get_claude_credentials().map_err(|_| {
             CswitchError::Keychain(
-                "No Claude Code credentials found in Keychain. Log in to Claude Code first.".into(),
+                "No Claude Code credentials found. Log in to Claude Code first.".into(),
             )
         })?;
         keychain::set_oauth_token(&name, &creds)?;
+        oauth_account = snapshot_oauth_account();
         ProfileType::OAuth
-    } else {
+    } else if selected == CLAUDE_LOGIN_OPTION {
         crate::oauth::run_oauth_flow(&name)?;
+        oauth_account = snapshot_oauth_account();
         ProfileType::OAuth
+    } else if selected == "Amazon Bedrock" {
+        prompt_bedrock()?
+    } else if selected == "Google Vertex AI" {
+        prompt_vertex()?
+    } else if selected.starts_with("Gateway") {
+        let base_url = prompt_text("Gateway base URL", None, false)?;
+        // Short-lived tokens are best stored as a cmd: reference
+        let token = prompt_secret("Bearer token (or env:VAR, file:PATH, cmd:COMMAND to fetch one)")?;
+        keychain::set_auth_token(&name, &token)?;
+        ProfileType::Gateway { base_url }
+    } else if selected == SETUP_TOKEN_OPTION {
+        let token = prompt_setup_token()?;
+        let expires_at = prompt_expiry()?;
+        keychain::set_setup_token(&name, &token)?;
+        ProfileType::SetupToken {
+            expires_at: Some(expires_at),
+        }
+    } else {
+        oauth_account = crate::oauth::run_native_oauth_flow(&name)?;
+        ProfileType::OAuth
+    };
+
+    let account = match profile_type {
+        ProfileType::OAuth => Account::from_login(&keychain::get_oauth_token(&name)?, oauth_account.as_ref()),
+        _ => None,
     };
 
     if updating {
         // Keep existing label and created_at, just update the type
-        if let Some(existing) = store.profiles.get_mut(&name) {
-            existing.profile_type = profile_type;
-        }
-        store.save()?;
+        let previous = store.update(|store| {
+            let existing = store
+                .profiles
+                .get_mut(&name)
+                .ok_or_else(|| CswitchError::ProfileNotFound(name.clone()))?;
+            existing.oauth_account = oauth_account;
+            existing.account = account;
+            Ok(std::mem::replace(&mut existing.profile_type, profile_type.clone()))
+        })?;
+        delete_replaced_secret(&name, &previous, &profile_type)?;
         println!("{} Profile '{}' updated.", "✓".green().bold(), name);
     } else {
         let label: String = Input::new()
@@ -93,16 +142,182 @@ pub fn run(name: Option<String>) -> Result<()> {
 
         let label = if label.is_empty() { None } else { Some(label) };
 
-        let profile = Profile {
-            name: name.clone(),
-            profile_type,
-            label,
-            created_at: Utc::now(),
-            last_used: None,
-        };
+        let mut profile = Profile::new(name.clone(), profile_type, label);
+        profile.oauth_account = oauth_account;
+        profile.account = account;
 
         store.add_profile(profile)?;
         println!("{} Profile '{}' added.", "✓".green().bold(), name);
     }
     Ok(())
 }
+
+/// Delete the secret a profile kept for its `previous` type once it has
+/// switched to another type that keeps a different kind, or none.
+fn delete_replaced_secret(name: &str, previous: &ProfileType, current: &ProfileType) -> Result<()> {
+    if keychain::secret_kind(previous) == keychain::secret_kind(current) {
+        return Ok(());
+    }
+    keychain::delete_profile_secret(name, previous)
+}
+
+/// The account Claude Code is logged in with, as recorded in ~/.claude.json,
+/// to go with the OAuth credentials just taken from it.
+pub fn snapshot_oauth_account() -> Option<serde_json::Value> {
+    match claude_config::read_oauth_account() {
+        Ok(account) => account,
+        Err(e) => {
+            eprintln!(
+                "{} Could not read the account from ~/.claude.json: {e}",
+                "Warning:".yellow().bold()
+            );
+            None
+        }
+    }
+}
+
+/// Bedrock uses the AWS SDK credential chain, so only the profile and region
+/// are needed; models are optional overrides.
+fn prompt_bedrock() -> Result<ProfileType> {
+    let aws_profile = prompt_text("AWS profile (optional)", std::env::var("AWS_PROFILE").ok(), true)?;
+    let region = prompt_text(
+        "AWS region",
+        Some(std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".into())),
+        false,
+    )?;
+    let model = prompt_text("Model ID or inference profile ARN (optional)", None, true)?;
+    let small_fast_model = prompt_text("Small/fast model ID or ARN (optional)", None, true)?;
+
+    Ok(ProfileType::Bedrock {
+        aws_profile: non_empty(aws_profile),
+        region,
+        model: non_empty(model),
+        small_fast_model: non_empty(small_fast_model),
+    })
+}
+
+/// Vertex uses gcloud application default credentials.
+fn prompt_vertex() -> Result<ProfileType> {
+    let project_id = prompt_text(
+        "GCP project ID",
+        std::env::var("ANTHROPIC_VERTEX_PROJECT_ID")
+            .or_else(|_| std::env::var("GOOGLE_CLOUD_PROJECT"))
+            .ok(),
+        false,
+    )?;
+    let region = prompt_text("Region", Some("us-east5".into()), false)?;
+    Ok(ProfileType::Vertex { project_id, region })
+}
+
+/// `echo "$TOKEN" | cswitch add ci --setup-token`: no prompts, so build
+/// scripts can re-run it to replace the token.
+fn add_piped_setup_token(name: Option<String>) -> Result<()> {
+    let name = name.ok_or_else(|| {
+        CswitchError::Unsupported("Pass the profile name when piping a token: cswitch add <name> --setup-token".into())
+    })?;
+    profile::validate_name(&name)?;
+
+    let mut token = String::new();
+    std::io::stdin().read_to_string(&mut token)?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(CswitchError::Unsupported("No token on stdin".into()));
+    }
+    warn_unless_setup_token(token);
+
+    keychain::set_setup_token(&name, token)?;
+    let profile_type = ProfileType::SetupToken {
+        expires_at: Some(Utc::now() + Duration::days(SETUP_TOKEN_LIFETIME_DAYS)),
+    };
+
+    let mut store = ProfileStore::load()?;
+    let previous = store.update(|store| {
+        Ok(match store.profiles.get_mut(&name) {
+            Some(existing) => Some(std::mem::replace(&mut existing.profile_type, profile_type.clone())),
+            None => {
+                store.profiles.insert(name.clone(), Profile::new(name.clone(), profile_type.clone(), None));
+                None
+            }
+        })
+    })?;
+    if let Some(previous) = &previous {
+        delete_replaced_secret(&name, previous, &profile_type)?;
+    }
+
+    let verb = if previous.is_some() { "updated" } else { "added" };
+    println!("{} Profile '{}' {verb}.", "✓".green().bold(), name);
+    Ok(())
+}
+
+fn prompt_setup_token() -> Result<String> {
+    let sources = ["Run 'claude setup-token'", "Paste a token"];
+    let choice = Select::new()
+        .with_prompt("Token")
+        .items(&sources)
+        .default(0)
+        .interact()
+        .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+
+    let token = if choice == 0 {
+        crate::oauth::run_setup_token_flow()?
+    } else {
+        Password::new()
+            .with_prompt("Token from 'claude setup-token'")
+            .interact()
+            .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?
+            .trim()
+            .to_string()
+    };
+    warn_unless_setup_token(&token);
+    Ok(token)
+}
+
+fn warn_unless_setup_token(token: &str) {
+    if !token.starts_with("sk-ant-oat") {
+        eprintln!(
+            "{} Token doesn't start with 'sk-ant-oat'. Storing anyway.",
+            "Warning:".yellow().bold()
+        );
+    }
+}
+
+/// Setup tokens don't carry their expiry, so default to a year from now.
+fn prompt_expiry() -> Result<chrono::DateTime<Utc>> {
+    let default = (Utc::now() + Duration::days(SETUP_TOKEN_LIFETIME_DAYS)).format("%Y-%m-%d");
+    let date = prompt_text("Expires on (YYYY-MM-DD)", Some(default.to_string()), false)?;
+    NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|e| CswitchError::Unsupported(format!("Invalid date '{date}': {e}")))
+}
+
+/// Read a secret, accepting a reference in its place.
+fn prompt_secret(prompt: &str) -> Result<String> {
+    let secret = Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+    let secret = secret.trim().to_string();
+
+    // The reference may only resolve in another environment, so just warn
+    if reference::is_reference(&secret) {
+        if let Err(e) = reference::resolve(&secret) {
+            eprintln!("{} {e}. Storing anyway.", "Warning:".yellow().bold());
+        }
+    }
+    Ok(secret)
+}
+
+fn prompt_text(prompt: &str, default: Option<String>, allow_empty: bool) -> Result<String> {
+    let mut input = Input::<String>::new().with_prompt(prompt).allow_empty(allow_empty);
+    if let Some(default) = default {
+        input = input.default(default);
+    }
+    let value = input
+        .interact_text()
+        .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+    Ok(value.trim().to_string())
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() { None } else { Some(s) }
+}
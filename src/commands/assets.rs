@@ -0,0 +1,91 @@
+use colored::Colorize;
+
+use crate::assets::{self, ASSETS};
+use crate::claude_config;
+use crate::cli::AssetsCommand;
+use crate::error::{CswitchError, Result};
+use crate::isolated;
+use crate::profile::{Profile, ProfileStore};
+
+pub fn run(command: AssetsCommand) -> Result<()> {
+    match command {
+        AssetsCommand::Capture { profile, only } => capture(profile, only),
+        AssetsCommand::Status => status(),
+    }
+}
+
+/// Where a profile's assets are live: its own directory in isolated mode.
+fn live_dir(profile: &Profile) -> Result<std::path::PathBuf> {
+    if profile.isolated {
+        isolated::profile_config_dir(&profile.name)
+    } else {
+        claude_config::claude_dir()
+    }
+}
+
+/// Copy the live assets into the profile's set, replacing what it had.
+fn capture(name: String, only: Vec<String>) -> Result<()> {
+    let store = ProfileStore::load()?;
+    let profile = store.get_profile(&name)?;
+    if let Some(unknown) = only.iter().find(|n| !ASSETS.contains(&n.as_str())) {
+        return Err(CswitchError::Unsupported(format!(
+            "Unknown asset '{unknown}'. Expected one of: {}",
+            ASSETS.join(", ")
+        )));
+    }
+
+    let live_dir = live_dir(profile)?;
+    let profile_dir = assets::profile_dir(&name)?;
+    let mut captured = Vec::new();
+    for &asset in ASSETS.iter().filter(|a| only.is_empty() || only.iter().any(|o| o == *a)) {
+        let live = live_dir.join(asset);
+        // Already the profile's own copy, or nothing to take
+        if assets::linked_profile(&live).as_deref() == Some(name.as_str()) || !live.exists() {
+            continue;
+        }
+        let dest = profile_dir.join(asset);
+        assets::remove(&dest)?;
+        std::fs::create_dir_all(&profile_dir)?;
+        assets::copy_all(&live, &dest)?;
+        captured.push(asset);
+    }
+
+    if captured.is_empty() {
+        println!("Nothing to capture for '{name}'.");
+    } else {
+        println!(
+            "{} Captured {} for '{}' into {}.",
+            "✓".green().bold(),
+            captured.join(", "),
+            name,
+            profile_dir.display()
+        );
+        println!("  They are linked in on 'cswitch use {name}'.");
+    }
+    Ok(())
+}
+
+/// Show what each live asset is and what the active profile has.
+fn status() -> Result<()> {
+    let store = ProfileStore::load()?;
+    let profile = store.get_active()?;
+    let live_dir = live_dir(profile)?;
+    let owned = assets::profile_assets(&profile.name)?;
+
+    println!("{} {}", "Active:".bold(), profile.name.green().bold());
+    println!("  {}", assets::profile_dir(&profile.name)?.display().to_string().dimmed());
+    for &asset in ASSETS {
+        let live = live_dir.join(asset);
+        let state = match assets::linked_profile(&live) {
+            Some(linked) => format!("linked to '{linked}'"),
+            None if store.linked_assets.iter().any(|l| l == asset) && live.exists() => {
+                "replaced since linked (conflict)".yellow().to_string()
+            }
+            None if live.exists() => "yours".to_string(),
+            None => "absent".dimmed().to_string(),
+        };
+        let has = if owned.contains(&asset) { "profile has one" } else { "profile has none" };
+        println!("  {:<10} {}  {}", asset, state, has.dimmed());
+    }
+    Ok(())
+}
@@ -1,29 +1,38 @@
 use colored::Colorize;
 
 use crate::error::Result;
+use crate::isolated;
 use crate::keychain;
+use crate::live::{self, Login};
+use crate::pin::{self, Source};
 use crate::profile::{ProfileStore, ProfileType};
+use crate::secrets::reference;
 
 pub fn run() -> Result<()> {
     let store = ProfileStore::load()?;
-    let profile = store.get_active()?;
-
-    let masked_credential = match profile.profile_type {
-        ProfileType::ApiKey => {
-            match keychain::get_api_key(&profile.name) {
-                Ok(key) => {
-                    if key.len() > 10 {
-                        let start = &key[..7];
-                        let end = &key[key.len() - 4..];
-                        format!("{}...{}", start, end)
-                    } else {
-                        "***".to_string()
-                    }
-                }
-                Err(_) => "key not found in keychain".to_string(),
+    let resolved = pin::resolve(&store)?;
+    let profile = resolved.profile;
+
+    let masked_credential = match &profile.profile_type {
+        ProfileType::ApiKey => match keychain::get_raw_api_key(&profile.name) {
+            Ok(key) => mask(&key),
+            Err(_) => "key not found in keychain".to_string(),
+        },
+        ProfileType::OAuth => "oauth token".to_string(),
+        ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => {
+            profile.profile_type.details().unwrap_or_default()
+        }
+        ProfileType::Gateway { base_url } => match keychain::get_raw_auth_token(&profile.name) {
+            Ok(token) => format!("{base_url}, {}", mask(&token)),
+            Err(_) => format!("{base_url}, token not found in keychain"),
+        },
+        ProfileType::SetupToken { .. } => {
+            let details = profile.profile_type.details().unwrap_or_default();
+            match keychain::get_setup_token(&profile.name) {
+                Ok(token) => format!("{}, {details}", mask(&token)),
+                Err(_) => format!("token not found in keychain, {details}"),
             }
         }
-        ProfileType::OAuth => "oauth token".to_string(),
     };
 
     let label_str = profile
@@ -41,5 +50,79 @@ pub fn run() -> Result<()> {
         masked_credential.dimmed()
     );
 
+    if let Some(account) = &profile.account {
+        let identity = match (&account.email, &account.organization) {
+            (Some(email), Some(org)) => Some(format!("{email} ({org})")),
+            (Some(email), None) => Some(email.clone()),
+            (None, Some(org)) => Some(org.clone()),
+            (None, None) => None,
+        };
+        if let Some(identity) = identity {
+            println!("  {} {}", "account:".dimmed(), identity);
+        }
+        let plan = match (&account.subscription_type, &account.rate_limit_tier) {
+            (Some(plan), Some(tier)) => Some(format!("{plan}, rate limit tier {tier}")),
+            (Some(plan), None) => Some(plan.clone()),
+            (None, Some(tier)) => Some(format!("rate limit tier {tier}")),
+            (None, None) => None,
+        };
+        if let Some(plan) = plan {
+            println!("  {} {}", "plan:".dimmed(), plan);
+        }
+        if !account.scopes.is_empty() {
+            println!("  {} {}", "scopes:".dimmed(), account.scopes.join(", "));
+        }
+    }
+
+    if profile.isolated {
+        println!(
+            "  {} {}",
+            "config dir:".dimmed(),
+            isolated::profile_config_dir(&profile.name)?.display()
+        );
+    }
+
+    if !profile.env.is_empty() {
+        let keys: Vec<_> = profile.env.keys().map(String::as_str).collect();
+        println!("  {} {}", "env:".dimmed(), keys.join(", "));
+    }
+
+    if !profile.settings.is_empty() {
+        let keys: Vec<_> = profile.settings.keys().map(String::as_str).collect();
+        println!("  {} {}", "settings:".dimmed(), keys.join(", "));
+    }
+
+    if !matches!(resolved.source, Source::Active) {
+        println!("  {}", resolved.source.to_string().dimmed());
+        match store.active.as_deref() {
+            Some(active) if active != profile.name => {
+                println!("  {} {}", "global active:".dimmed(), active)
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(login) = Login::read().ok().flatten() {
+        if let Some(drift) = live::drift(&store, &login) {
+            println!(
+                "  {} {drift}. Run 'cswitch reconcile'.",
+                "Warning:".yellow().bold()
+            );
+        }
+    }
+
     Ok(())
 }
+
+fn mask(secret: &str) -> String {
+    // References aren't secret; show where the value comes from
+    if reference::is_reference(secret) {
+        secret.to_string()
+    } else if secret.len() > 10 {
+        let start = &secret[..7];
+        let end = &secret[secret.len() - 4..];
+        format!("{}...{}", start, end)
+    } else {
+        "***".to_string()
+    }
+}
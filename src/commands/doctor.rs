@@ -0,0 +1,436 @@
+use chrono::{DateTime, Local};
+use colored::Colorize;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::claude_config::{self, API_KEY_HELPER_CMD};
+use crate::error::{CswitchError, Result};
+use crate::isolated;
+use crate::keychain;
+use crate::profile::{Profile, ProfileStore, ProfileType};
+use crate::secrets::{self, reference};
+
+#[derive(PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+type Repair = Box<dyn FnOnce() -> Result<()>>;
+
+/// One diagnostic result, with a hint and optionally a safe automatic repair.
+struct Check {
+    status: Status,
+    message: String,
+    fix: Option<String>,
+    repair: Option<Repair>,
+}
+
+impl Check {
+    fn pass(message: impl Into<String>) -> Self {
+        Check { status: Status::Pass, message: message.into(), fix: None, repair: None }
+    }
+
+    fn warn(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { status: Status::Warn, message: message.into(), fix: Some(fix.into()), repair: None }
+    }
+
+    fn fail(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { status: Status::Fail, message: message.into(), fix: Some(fix.into()), repair: None }
+    }
+
+    fn repair(mut self, repair: impl FnOnce() -> Result<()> + 'static) -> Self {
+        self.repair = Some(Box::new(repair));
+        self
+    }
+}
+
+pub fn run(fix: bool) -> Result<()> {
+    let mut checks = vec![check_claude_installed(), check_cswitch_on_path(), check_env_api_key()];
+
+    match ProfileStore::load() {
+        Ok(store) => {
+            checks.extend(check_active_profile(&store));
+            checks.extend(check_stored_secrets(&store));
+        }
+        Err(e) => checks.push(Check::fail(
+            format!("Cannot read profiles.json: {e}"),
+            "Fix or remove the file, then run 'cswitch init'",
+        )),
+    }
+    checks.extend(check_permissions()?);
+
+    let (mut passed, mut warnings, mut failed) = (0, 0, 0);
+    for check in checks {
+        let symbol = match check.status {
+            Status::Pass => "✓".green().bold(),
+            Status::Warn => "!".yellow().bold(),
+            Status::Fail => "✗".red().bold(),
+        };
+        println!("{symbol} {}", check.message);
+
+        let mut status = check.status;
+        match (check.repair, fix) {
+            (Some(repair), true) => match repair() {
+                Ok(()) => {
+                    println!("    {}", "fixed".green());
+                    status = Status::Pass;
+                }
+                Err(e) => println!("    {} {e}", "fix failed:".red()),
+            },
+            (Some(_), false) => {
+                if let Some(hint) = check.fix {
+                    println!("    {} {hint} (or run 'cswitch doctor --fix')", "fix:".dimmed());
+                }
+            }
+            (None, _) => {
+                if let Some(hint) = check.fix {
+                    println!("    {} {hint}", "fix:".dimmed());
+                }
+            }
+        }
+
+        match status {
+            Status::Pass => passed += 1,
+            Status::Warn => warnings += 1,
+            Status::Fail => failed += 1,
+        }
+    }
+
+    println!();
+    println!("{passed} passed, {warnings} warning(s), {failed} failed");
+
+    if failed > 0 {
+        return Err(CswitchError::DoctorFailed(failed));
+    }
+    Ok(())
+}
+
+fn check_claude_installed() -> Check {
+    match Command::new("claude").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Check::pass(format!("Claude Code installed ({version})"))
+        }
+        _ => Check::fail(
+            "Claude Code CLI ('claude') not found",
+            "Install it: https://docs.anthropic.com/en/docs/claude-code",
+        ),
+    }
+}
+
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            fs::metadata(candidate)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+}
+
+fn check_cswitch_on_path() -> Check {
+    let Some(found) = find_on_path("cswitch") else {
+        return Check::fail(
+            "cswitch is not on PATH, so Claude Code can't run 'cswitch emit-key'",
+            "Add the directory containing cswitch to PATH in your shell profile",
+        );
+    };
+
+    let current = std::env::current_exe().and_then(|p| p.canonicalize()).ok();
+    match (found.canonicalize().ok(), current) {
+        (Some(found_real), Some(current)) if found_real != current => Check::warn(
+            format!("'cswitch' on PATH is {}, not this binary ({})", found.display(), current.display()),
+            "Remove the stale copy or reorder PATH",
+        ),
+        _ => Check::pass(format!("cswitch on PATH ({})", found.display())),
+    }
+}
+
+fn check_env_api_key() -> Check {
+    if std::env::var_os("ANTHROPIC_API_KEY").is_some() {
+        Check::warn(
+            "ANTHROPIC_API_KEY is exported and overrides every cswitch profile",
+            "unset ANTHROPIC_API_KEY and remove it from your shell profile",
+        )
+    } else {
+        Check::pass("ANTHROPIC_API_KEY is not set")
+    }
+}
+
+fn check_active_profile(store: &ProfileStore) -> Vec<Check> {
+    if store.profiles.is_empty() {
+        return vec![Check::warn("No profiles configured", "cswitch add")];
+    }
+    let Some(name) = store.active.as_deref() else {
+        return vec![Check::warn("No active profile", "cswitch use <name>")];
+    };
+    let Some(profile) = store.profiles.get(name) else {
+        return vec![Check::fail(
+            format!("Active profile '{name}' is missing from profiles.json"),
+            "cswitch use <name>",
+        )];
+    };
+
+    let mut checks = vec![Check::pass(format!("Active profile: {name} ({})", profile.profile_type))];
+    if profile.isolated {
+        // The global settings.json belongs to whichever profile was used last
+        checks.extend(match &profile.profile_type {
+            ProfileType::ApiKey => Some(check_api_key(profile)),
+            ProfileType::OAuth => Some(check_oauth_token(profile)),
+            ProfileType::Gateway { .. } => Some(check_auth_token(profile)),
+            ProfileType::SetupToken { .. } => Some(check_setup_token(profile)),
+            ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => None,
+        });
+        checks.push(check_isolated_dir(profile));
+        return checks;
+    }
+    let helper = claude_config::api_key_helper();
+
+    match &profile.profile_type {
+        ProfileType::ApiKey => {
+            checks.push(check_api_key(profile));
+            checks.push(check_helper(helper, name));
+        }
+        ProfileType::OAuth => {
+            checks.push(check_oauth_token(profile));
+            checks.push(check_no_helper(helper, name, "the OAuth login"));
+        }
+        ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => {
+            checks.push(check_no_helper(helper, name, "the cloud provider"));
+            checks.push(check_provider_env(profile));
+        }
+        ProfileType::Gateway { .. } => {
+            checks.push(check_auth_token(profile));
+            checks.push(check_helper(helper, name));
+            checks.push(check_provider_env(profile));
+        }
+        ProfileType::SetupToken { .. } => {
+            checks.push(check_setup_token(profile));
+            checks.push(check_no_helper(helper, name, "the setup token"));
+        }
+    }
+    checks
+}
+
+fn check_isolated_dir(profile: &Profile) -> Check {
+    let name = &profile.name;
+    let dir = match isolated::profile_config_dir(name) {
+        Ok(dir) => dir,
+        Err(e) => return Check::fail(format!("{e}"), "Set HOME or XDG_DATA_HOME"),
+    };
+    if !dir.exists() {
+        Check::fail(
+            format!("Isolated config directory {} is missing", dir.display()),
+            format!("cswitch use {name}"),
+        )
+    } else if isolated::current_profile().as_deref() != Some(name.as_str()) {
+        Check::warn(
+            format!("CLAUDE_CONFIG_DIR doesn't point at {} in this shell", dir.display()),
+            format!("eval \"$(cswitch hook bash)\" in your shell profile, or eval \"$(cswitch env {name})\""),
+        )
+    } else {
+        Check::pass(format!("CLAUDE_CONFIG_DIR is {}", dir.display()))
+    }
+}
+
+fn check_helper(helper: Result<Option<String>>, name: &str) -> Check {
+    match helper {
+        Ok(Some(cmd)) if cmd == API_KEY_HELPER_CMD => {
+            Check::pass(format!("apiKeyHelper is '{API_KEY_HELPER_CMD}'"))
+        }
+        Ok(other) => Check::fail(
+            match other {
+                Some(cmd) => format!("apiKeyHelper is '{cmd}', expected '{API_KEY_HELPER_CMD}'"),
+                None => "apiKeyHelper is not set in settings.json".to_string(),
+            },
+            format!("cswitch use {name}"),
+        )
+        .repair(claude_config::enable_api_key_helper),
+        Err(e) => Check::fail(format!("Cannot read settings.json: {e}"), "Fix the JSON syntax"),
+    }
+}
+
+fn check_no_helper(helper: Result<Option<String>>, name: &str, overridden: &str) -> Check {
+    match helper {
+        Ok(Some(cmd)) if cmd == API_KEY_HELPER_CMD => Check::fail(
+            format!("apiKeyHelper is still set, so Claude Code ignores {overridden}"),
+            format!("cswitch use {name}"),
+        )
+        .repair(claude_config::disable_api_key_helper),
+        Ok(_) => Check::pass(format!("apiKeyHelper is not overriding {overridden}")),
+        Err(e) => Check::fail(format!("Cannot read settings.json: {e}"), "Fix the JSON syntax"),
+    }
+}
+
+/// The provider variables must be in settings.json for Claude Code to use
+/// Bedrock, Vertex or the gateway instead of the Anthropic API.
+fn check_provider_env(profile: &Profile) -> Check {
+    let settings = match claude_config::read_settings() {
+        Ok(settings) => settings,
+        Err(e) => return Check::fail(format!("Cannot read settings.json: {e}"), "Fix the JSON syntax"),
+    };
+    // The profile's own env block may override a provider variable
+    let expected = profile.settings_env();
+    let missing: Vec<_> = profile
+        .profile_type
+        .provider_env()
+        .into_keys()
+        .filter(|k| settings["env"][k].as_str() != expected.get(k).map(String::as_str))
+        .collect();
+    if missing.is_empty() {
+        Check::pass(format!("settings.json selects {}", profile.profile_type))
+    } else {
+        Check::fail(
+            format!("settings.json env is missing or has different {}", missing.join(", ")),
+            format!("cswitch use {}", profile.name),
+        )
+    }
+}
+
+fn check_api_key(profile: &Profile) -> Check {
+    let name = &profile.name;
+    match keychain::get_raw_api_key(name) {
+        Ok(key) if reference::is_reference(&key) => match reference::resolve(&key) {
+            Ok(_) => Check::pass(format!("API key reference '{key}' resolves")),
+            Err(e) => Check::fail(format!("{e}"), format!("Fix the reference or run 'cswitch add {name}'")),
+        },
+        Ok(_) => Check::pass(format!("API key stored for '{name}'")),
+        Err(_) => Check::fail(
+            format!("No API key stored for active profile '{name}'"),
+            format!("cswitch add {name}"),
+        ),
+    }
+}
+
+fn check_auth_token(profile: &Profile) -> Check {
+    let name = &profile.name;
+    match keychain::get_raw_auth_token(name) {
+        Ok(token) if reference::is_reference(&token) => match reference::resolve(&token) {
+            Ok(_) => Check::pass(format!("Gateway token reference '{token}' resolves")),
+            Err(e) => Check::fail(format!("{e}"), format!("Fix the reference or run 'cswitch add {name}'")),
+        },
+        Ok(_) => Check::pass(format!("Gateway token stored for '{name}'")),
+        Err(_) => Check::fail(
+            format!("No gateway token stored for active profile '{name}'"),
+            format!("cswitch add {name}"),
+        ),
+    }
+}
+
+fn check_setup_token(profile: &Profile) -> Check {
+    let name = &profile.name;
+    if keychain::get_setup_token(name).is_err() {
+        return Check::fail(
+            format!("No setup token stored for active profile '{name}'"),
+            format!("cswitch add {name}"),
+        );
+    }
+    let fix = format!("claude setup-token, then cswitch add {name}");
+    match &profile.profile_type {
+        ProfileType::SetupToken { expires_at: Some(at) } if *at <= chrono::Utc::now() => {
+            Check::fail(format!("Setup token expired on {}", at.format("%Y-%m-%d")), fix)
+        }
+        _ => match profile.expiry_warning() {
+            Some(warning) => Check::warn(warning, fix),
+            None => Check::pass(format!(
+                "Setup token stored ({})",
+                profile.profile_type.details().unwrap_or_default()
+            )),
+        },
+    }
+}
+
+fn check_oauth_token(profile: &Profile) -> Check {
+    let name = &profile.name;
+    let token = match keychain::get_oauth_token(name) {
+        Ok(token) => token,
+        Err(_) => {
+            return Check::fail(
+                format!("No OAuth token stored for active profile '{name}'"),
+                format!("cswitch add {name}"),
+            )
+        }
+    };
+    match keychain::oauth_token_expiry(&token) {
+        None => Check::fail(
+            format!("OAuth token for '{name}' is malformed"),
+            format!("cswitch add {name}"),
+        ),
+        Some((expires_at, has_refresh)) => {
+            let expiry = DateTime::from_timestamp_millis(expires_at)
+                .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            match (keychain::oauth_token_expiring(expires_at), has_refresh) {
+                (false, _) => Check::pass(format!("OAuth token valid until {expiry}")),
+                (true, true) => Check::pass(format!(
+                    "OAuth token expired {expiry}; it will be refreshed on next 'cswitch use'"
+                )),
+                (true, false) => Check::fail(
+                    format!("OAuth token expired {expiry} and has no refresh token"),
+                    format!("cswitch add {name} to log in again"),
+                ),
+            }
+        }
+    }
+}
+
+/// Profiles other than the active one that have nothing stored.
+fn check_stored_secrets(store: &ProfileStore) -> Vec<Check> {
+    let mut profiles: Vec<_> = store
+        .profiles
+        .values()
+        .filter(|p| store.active.as_deref() != Some(&p.name))
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    profiles
+        .into_iter()
+        .filter(|p| match p.profile_type {
+            ProfileType::ApiKey => keychain::get_raw_api_key(&p.name).is_err(),
+            ProfileType::OAuth => keychain::get_oauth_token(&p.name).is_err(),
+            ProfileType::Gateway { .. } => keychain::get_raw_auth_token(&p.name).is_err(),
+            ProfileType::SetupToken { .. } => keychain::get_setup_token(&p.name).is_err(),
+            ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => false,
+        })
+        .map(|p| {
+            Check::warn(
+                format!("Profile '{}' has no credentials stored", p.name),
+                format!("cswitch add {} (or cswitch remove {})", p.name, p.name),
+            )
+        })
+        .collect()
+}
+
+fn check_permissions() -> Result<Vec<Check>> {
+    let mut files = secrets::credential_files()?;
+    if let Some(path) = keychain::claude_credentials_file()? {
+        if path.exists() {
+            files.push(path);
+        }
+    }
+    Ok(files.into_iter().map(|path| check_private(&path)).collect())
+}
+
+fn check_private(path: &Path) -> Check {
+    let mode = match fs::metadata(path) {
+        Ok(m) => m.permissions().mode() & 0o777,
+        Err(e) => return Check::fail(format!("Cannot stat {}: {e}", path.display()), "Check the file"),
+    };
+    if mode & 0o077 == 0 {
+        return Check::pass(format!("{} is owner-only ({mode:o})", path.display()));
+    }
+    let target = path.to_path_buf();
+    Check::fail(
+        format!("{} is accessible by other users ({mode:o})", path.display()),
+        format!("chmod 600 {}", path.display()),
+    )
+    .repair(move || {
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600))?;
+        Ok(())
+    })
+}
@@ -1,13 +1,30 @@
-use crate::error::Result;
+use crate::error::{CswitchError, Result};
+use crate::isolated;
 use crate::keychain;
-use crate::profile::ProfileStore;
+use crate::pin;
+use crate::profile::{ProfileStore, ProfileType};
 
-/// Hidden command: prints the active API key to stdout.
+/// Hidden command: prints the API key or gateway token of the profile that
+/// applies in the current directory (pinned or active) to stdout, resolving
+/// `env:`/`file:`/`cmd:` references. Inside an isolated profile's config
+/// directory, that profile always applies.
 /// Used by `apiKeyHelper: "cswitch emit-key"` in Claude settings.json.
 pub fn run() -> Result<()> {
     let store = ProfileStore::load()?;
-    let profile = store.get_active()?;
-    let key = keychain::get_api_key(&profile.name)?;
+    let profile = match isolated::current_profile() {
+        Some(name) => store.get_profile(&name)?,
+        None => pin::resolve(&store)?.profile,
+    };
+    let key = match profile.profile_type {
+        ProfileType::ApiKey => keychain::get_api_key(&profile.name)?,
+        ProfileType::Gateway { .. } => keychain::get_auth_token(&profile.name)?,
+        _ => {
+            return Err(CswitchError::Unsupported(format!(
+                "Profile '{}' is {}, not an API key or gateway profile. Run 'cswitch use {}'.",
+                profile.name, profile.profile_type, profile.name
+            )))
+        }
+    };
     print!("{key}");
     Ok(())
 }
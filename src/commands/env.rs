@@ -0,0 +1,71 @@
+use serde_json::{Map, Value};
+
+use crate::cli::EnvShell;
+use crate::error::Result;
+use crate::pin;
+use crate::profile::ProfileStore;
+use crate::profile_env::{self, EnvVar};
+
+/// Print shell code that exports (or, with `unset`, clears) the variables for
+/// `profile`, or for the profile that applies here.
+pub fn run(profile: Option<String>, shell: EnvShell, unset: bool) -> Result<()> {
+    let store = ProfileStore::load()?;
+    let profile = match profile {
+        Some(name) => store.get_profile(&name)?,
+        None => pin::resolve(&store)?.profile,
+    };
+
+    let vars: Vec<EnvVar> = if unset {
+        profile_env::names(profile).into_iter().map(|n| (n, None)).collect()
+    } else {
+        profile_env::for_profile(profile)?
+    };
+
+    print!("{}", render(&vars, shell));
+    Ok(())
+}
+
+pub fn render(vars: &[EnvVar], shell: EnvShell) -> String {
+    if let EnvShell::Json = shell {
+        let obj: Map<String, Value> = vars
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone().map(Value::String).unwrap_or(Value::Null)))
+            .collect();
+        return format!("{}\n", Value::Object(obj));
+    }
+
+    vars.iter()
+        .map(|(name, value)| {
+            let line = match (shell, value) {
+                (EnvShell::Bash, Some(v)) => format!("export {name}={}", posix_quote(v)),
+                (EnvShell::Bash, None) => format!("unset {name}"),
+                (EnvShell::Fish, Some(v)) => format!("set -gx {name} {}", fish_quote(v)),
+                (EnvShell::Fish, None) => format!("set -e {name}"),
+                (EnvShell::Powershell, Some(v)) => format!("$env:{name} = '{}'", v.replace('\'', "''")),
+                (EnvShell::Powershell, None) => {
+                    format!("Remove-Item Env:{name} -ErrorAction SilentlyContinue")
+                }
+                (EnvShell::Dotenv, Some(v)) => format!("{name}={}", dotenv_quote(v)),
+                (EnvShell::Dotenv, None) => format!("{name}="),
+                (EnvShell::Json, _) => unreachable!(),
+            };
+            line + "\n"
+        })
+        .collect()
+}
+
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn dotenv_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n");
+    format!("\"{escaped}\"")
+}
@@ -0,0 +1,31 @@
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::error::{CswitchError, Result};
+use crate::profile::ProfileStore;
+use crate::profile_env;
+
+/// Replace this process with `command`, running as `profile`. The global
+/// active profile and Claude Code's own login are left alone; since the
+/// command takes over the process, signals and the exit status are its own.
+pub fn run(profile: String, command: Vec<String>) -> Result<()> {
+    let store = ProfileStore::load()?;
+    let profile = store.get_profile(&profile)?;
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| CswitchError::Exec("No command given".into()))?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    for (name, value) in profile_env::for_profile(profile)? {
+        match value {
+            Some(value) => cmd.env(name, value),
+            None => cmd.env_remove(name),
+        };
+    }
+
+    // Only returns on failure
+    let err = cmd.exec();
+    Err(CswitchError::Exec(format!("Failed to run '{program}': {err}")))
+}
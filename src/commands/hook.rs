@@ -0,0 +1,123 @@
+use colored::Colorize;
+
+use crate::cli::{EnvShell, HookShell};
+use crate::commands::{env, use_profile};
+use crate::error::Result;
+use crate::isolated;
+use crate::pin;
+use crate::profile::ProfileStore;
+use crate::profile_env::EnvVar;
+
+// Each hook also wraps `cswitch` so that `cswitch use` of an isolated
+// profile updates CLAUDE_CONFIG_DIR in the current shell.
+
+const BASH_HOOK: &str = r#"_cswitch_hook() {
+  if [ "$PWD" != "${_CSWITCH_LAST_PWD:-}" ]; then
+    _CSWITCH_LAST_PWD="$PWD"
+    eval "$(command cswitch hook-switch --shell bash)"
+  fi
+}
+cswitch() {
+  command cswitch "$@"
+  local ret=$?
+  eval "$(command cswitch hook-switch --shell bash --no-switch)"
+  return $ret
+}
+case ";${PROMPT_COMMAND:-};" in
+  *";_cswitch_hook;"*) ;;
+  *) PROMPT_COMMAND="_cswitch_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#;
+
+const ZSH_HOOK: &str = r#"_cswitch_hook() {
+  eval "$(command cswitch hook-switch --shell zsh)"
+}
+cswitch() {
+  command cswitch "$@"
+  local ret=$?
+  eval "$(command cswitch hook-switch --shell zsh --no-switch)"
+  return $ret
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _cswitch_hook
+_cswitch_hook
+"#;
+
+const FISH_HOOK: &str = r#"function __cswitch_hook --on-variable PWD
+    command cswitch hook-switch --shell fish | source
+end
+function cswitch --wraps cswitch
+    command cswitch $argv
+    set -l ret $status
+    command cswitch hook-switch --shell fish --no-switch | source
+    return $ret
+end
+__cswitch_hook
+"#;
+
+/// Print the snippet to source from the shell's rc file.
+pub fn run(shell: HookShell) -> Result<()> {
+    let snippet = match shell {
+        HookShell::Bash => BASH_HOOK,
+        HookShell::Zsh => ZSH_HOOK,
+        HookShell::Fish => FISH_HOOK,
+    };
+    print!("{snippet}");
+    Ok(())
+}
+
+/// Hidden command run by the hook on every directory change. Does nothing —
+/// no writes, no network — unless a pin or rule selects a profile other than
+/// the active one. With `shell`, it then prints code that points
+/// `CLAUDE_CONFIG_DIR` at the active profile's directory if it is isolated,
+/// or clears it if it still points at another profile's.
+pub fn switch(shell: Option<HookShell>, no_switch: bool) -> Result<()> {
+    let mut store = ProfileStore::load()?;
+    if !no_switch {
+        switch_to_local(&mut store)?;
+    }
+    let Some(shell) = shell else {
+        return Ok(());
+    };
+
+    let active = store.active.as_deref().and_then(|name| store.profiles.get(name));
+    let current = std::env::var_os("CLAUDE_CONFIG_DIR");
+    let var: Option<EnvVar> = match active {
+        Some(profile) if profile.isolated => {
+            let dir = isolated::profile_config_dir(&profile.name)?;
+            (current.as_deref() != Some(dir.as_os_str()))
+                .then(|| ("CLAUDE_CONFIG_DIR".to_string(), Some(dir.to_string_lossy().into_owned())))
+        }
+        _ => isolated::current_profile().map(|_| ("CLAUDE_CONFIG_DIR".to_string(), None)),
+    };
+
+    if let Some(var) = var {
+        let shell = match shell {
+            HookShell::Bash | HookShell::Zsh => EnvShell::Bash,
+            HookShell::Fish => EnvShell::Fish,
+        };
+        print!("{}", env::render(&[var], shell));
+    }
+    Ok(())
+}
+
+fn switch_to_local(store: &mut ProfileStore) -> Result<()> {
+    let Some(resolved) = pin::resolve_local(store)? else {
+        return Ok(());
+    };
+    if store.active.as_deref() == Some(resolved.profile.name.as_str()) {
+        return Ok(());
+    }
+
+    let profile = resolved.profile.clone();
+    let source = resolved.source.to_string();
+    use_profile::switch(store, &profile)?;
+
+    eprintln!(
+        "{} switched to '{}' ({})",
+        "cswitch:".blue().bold(),
+        profile.name,
+        source
+    );
+    Ok(())
+}
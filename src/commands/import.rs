@@ -1,10 +1,9 @@
-use chrono::Utc;
 use colored::Colorize;
 use dialoguer::Input;
 
 use crate::error::{CswitchError, Result};
 use crate::keychain;
-use crate::profile::{Profile, ProfileStore, ProfileType};
+use crate::profile::{self, Account, Profile, ProfileStore, ProfileType};
 
 pub fn run(name: Option<String>) -> Result<()> {
     let mut store = ProfileStore::load()?;
@@ -16,6 +15,7 @@ pub fn run(name: Option<String>) -> Result<()> {
             .interact_text()
             .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?,
     };
+    profile::validate_name(&name)?;
 
     if store.profiles.contains_key(&name) {
         return Err(CswitchError::ProfileAlreadyExists(name));
@@ -23,7 +23,7 @@ pub fn run(name: Option<String>) -> Result<()> {
 
     let creds = keychain::get_claude_credentials().map_err(|_| {
         CswitchError::Keychain(
-            "No existing Claude Code credentials found in Keychain. Make sure you're logged in to Claude Code first.".into(),
+            "No existing Claude Code credentials found. Make sure you're logged in to Claude Code first.".into(),
         )
     })?;
 
@@ -37,13 +37,9 @@ pub fn run(name: Option<String>) -> Result<()> {
 
     let label = if label.is_empty() { None } else { Some(label) };
 
-    let profile = Profile {
-        name: name.clone(),
-        profile_type: ProfileType::OAuth,
-        label,
-        created_at: Utc::now(),
-        last_used: None,
-    };
+    let mut profile = Profile::new(name.clone(), ProfileType::OAuth, label);
+    profile.oauth_account = super::add::snapshot_oauth_account();
+    profile.account = Account::from_login(&creds, profile.oauth_account.as_ref());
 
     store.add_profile(profile)?;
 
@@ -0,0 +1,34 @@
+use colored::Colorize;
+
+use crate::error::{CswitchError, Result};
+use crate::isolated;
+use crate::profile::{self, ProfileStore};
+
+/// Turn isolated mode on or off for `name`. Turning it off keeps the
+/// directory, so Claude Code's history and settings there survive.
+pub fn run(name: String, off: bool) -> Result<()> {
+    profile::validate_name(&name)?;
+    let mut store = ProfileStore::load()?;
+    let profile = store.update(|store| {
+        let profile = store
+            .profiles
+            .get_mut(&name)
+            .ok_or_else(|| CswitchError::ProfileNotFound(name.clone()))?;
+        profile.isolated = !off;
+        Ok(profile.clone())
+    })?;
+    let active = store.active.as_deref() == Some(name.as_str());
+
+    if off {
+        println!("{} Profile '{}' uses the global Claude Code config again.", "✓".green().bold(), name);
+        if active {
+            println!("  Run 'cswitch use {name}' to switch the global config to it.");
+        }
+        return Ok(());
+    }
+
+    let dir = isolated::prepare(&profile)?;
+    println!("{} Profile '{}' now has its own config directory: {}", "✓".green().bold(), name, dir.display());
+    println!("  Claude Code uses it where CLAUDE_CONFIG_DIR is set: after 'cswitch use {name}' with the shell hook, in 'cswitch exec {name}', or with 'eval \"$(cswitch env {name})\"'.");
+    Ok(())
+}
@@ -22,14 +22,15 @@ pub fn run() -> Result<()> {
             " ".to_string()
         };
 
-        let label = profile
-            .label
-            .as_deref()
-            .unwrap_or("")
-            .to_string();
+        let label = profile.description();
+        let label = if profile.isolated {
+            format!("{label} [isolated]").trim_start().to_string()
+        } else {
+            label
+        };
 
         println!(
-            "{} {:<12} {:<10} {}",
+            "{} {:<12} {:<12} {}",
             marker,
             if is_active {
                 profile.name.bold().to_string()
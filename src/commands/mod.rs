@@ -1,9 +1,21 @@
 pub mod add;
+pub mod assets;
 pub mod current;
+pub mod doctor;
 pub mod emit_key;
+pub mod env;
+pub mod exec;
+pub mod hook;
 pub mod import;
 pub mod init;
+pub mod isolate;
 pub mod list;
+pub mod pin;
+pub mod profile_env;
+pub mod reconcile;
 pub mod remove;
+pub mod resolve;
+pub mod secrets;
+pub mod settings;
 pub mod update;
 pub mod use_profile;
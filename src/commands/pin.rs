@@ -0,0 +1,21 @@
+use colored::Colorize;
+
+use crate::error::Result;
+use crate::pin;
+use crate::profile::{self, ProfileStore};
+
+pub fn run(name: String) -> Result<()> {
+    profile::validate_name(&name)?;
+    let store = ProfileStore::load()?;
+    if !store.profiles.contains_key(&name) {
+        eprintln!(
+            "{} No profile named '{}' exists yet. Pinning anyway.",
+            "Warning:".yellow().bold(),
+            name
+        );
+    }
+
+    let path = pin::write(&std::env::current_dir()?, &name)?;
+    println!("{} Pinned '{}' in {}.", "✓".green().bold(), name, path.display());
+    Ok(())
+}
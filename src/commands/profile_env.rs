@@ -0,0 +1,59 @@
+use colored::Colorize;
+
+use crate::error::{CswitchError, Result};
+use crate::profile::ProfileStore;
+
+/// Add or change variables in a profile's `env` block.
+pub fn set(name: String, assignments: Vec<String>) -> Result<()> {
+    let mut pairs = Vec::new();
+    for assignment in assignments {
+        let (key, value) = assignment
+            .split_once('=')
+            .filter(|(k, _)| !k.is_empty())
+            .ok_or_else(|| CswitchError::Unsupported(format!("Expected KEY=VALUE, got '{assignment}'")))?;
+        pairs.push((key.to_string(), value.to_string()));
+    }
+
+    let mut store = ProfileStore::load()?;
+    store.update(|store| {
+        let profile = store
+            .profiles
+            .get_mut(&name)
+            .ok_or_else(|| CswitchError::ProfileNotFound(name.clone()))?;
+        profile.env.extend(pairs.iter().cloned());
+        Ok(())
+    })?;
+
+    for (key, _) in &pairs {
+        println!("{} {}: set {}", "✓".green().bold(), name, key);
+    }
+    print_apply_hint(&store, &name);
+    Ok(())
+}
+
+/// Remove variables from a profile's `env` block.
+pub fn unset(name: String, keys: Vec<String>) -> Result<()> {
+    let mut store = ProfileStore::load()?;
+    store.update(|store| {
+        let profile = store
+            .profiles
+            .get_mut(&name)
+            .ok_or_else(|| CswitchError::ProfileNotFound(name.clone()))?;
+        for key in &keys {
+            profile.env.remove(key);
+        }
+        Ok(())
+    })?;
+
+    for key in &keys {
+        println!("{} {}: unset {}", "✓".green().bold(), name, key);
+    }
+    print_apply_hint(&store, &name);
+    Ok(())
+}
+
+fn print_apply_hint(store: &ProfileStore, name: &str) {
+    if store.active.as_deref() == Some(name) {
+        println!("Run 'cswitch use {name}' to apply it to Claude Code.");
+    }
+}
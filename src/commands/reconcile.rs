@@ -0,0 +1,85 @@
+use colored::Colorize;
+use dialoguer::{Confirm, Input, Select};
+
+use crate::error::{CswitchError, Result};
+use crate::keychain;
+use crate::live::{self, Drift, Login};
+use crate::profile::{ProfileStore, ProfileType};
+
+use super::{import, use_profile};
+
+/// Make the active profile match the account Claude Code is really logged
+/// in as, after a `/login` or `claude auth login` cswitch didn't see: switch
+/// to the profile the login belongs to, or save an unknown one as a new
+/// profile.
+pub fn run() -> Result<()> {
+    let mut store = ProfileStore::load()?;
+
+    let Some(login) = Login::read()? else {
+        println!("Claude Code has no OAuth login; nothing to reconcile.");
+        return Ok(());
+    };
+
+    let name = match live::drift(&store, &login) {
+        None => {
+            match store.get_active() {
+                Ok(active) if active.profile_type == ProfileType::OAuth && !active.isolated => {
+                    let name = active.name.clone();
+                    live::sync_back(&mut store)?;
+                    println!(
+                        "{} In sync: Claude Code is logged in as '{}'.",
+                        "✓".green().bold(),
+                        name
+                    );
+                }
+                _ => println!("The active profile doesn't use Claude Code's login; nothing to reconcile."),
+            }
+            return Ok(());
+        }
+        Some(Drift::Other(names)) => {
+            println!("{}.", Drift::Other(names.clone()));
+            if names.len() == 1 {
+                names[0].clone()
+            } else {
+                let choice = Select::new()
+                    .with_prompt("Make which profile active")
+                    .items(&names)
+                    .default(0)
+                    .interact()
+                    .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+                names[choice].clone()
+            }
+        }
+        Some(drift @ Drift::Unknown(_)) => {
+            println!("{drift}.");
+            let capture = Confirm::new()
+                .with_prompt("Save this login as a new profile?")
+                .default(true)
+                .interact()
+                .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+            if !capture {
+                println!("Left as is.");
+                return Ok(());
+            }
+            let name: String = Input::new()
+                .with_prompt("Profile name")
+                .interact_text()
+                .map_err(|e| CswitchError::Config(format!("Input error: {e}")))?;
+            import::run(Some(name.clone()))?;
+            name
+        }
+    };
+
+    // The login is this profile's: keep it, so the switch writes it back as-is
+    keychain::store_oauth_token_if_newer(&name, &login.token_json)?;
+    store.update(|store| store.mark_active(&name))?;
+    let profile = store.get_profile(&name)?.clone();
+    use_profile::switch(&mut store, &profile)?;
+
+    println!(
+        "{} '{}' is now active, matching Claude Code's login.",
+        "✓".green().bold(),
+        name
+    );
+    Ok(())
+}
@@ -2,8 +2,11 @@ use colored::Colorize;
 use dialoguer::{Confirm, Select};
 
 use crate::error::{CswitchError, Result};
+use crate::assets;
+use crate::commands::use_profile;
+use crate::isolated;
 use crate::keychain;
-use crate::profile::{ProfileStore, ProfileType};
+use crate::profile::{self, ProfileStore};
 
 pub fn run(name: Option<String>) -> Result<()> {
     let mut store = ProfileStore::load()?;
@@ -23,8 +26,7 @@ pub fn run(name: Option<String>) -> Result<()> {
             let items: Vec<String> = profiles
                 .iter()
                 .map(|p| {
-                    let label = p.label.as_deref().unwrap_or("");
-                    format!("{} ({}) {}", p.name, p.profile_type, label)
+                    format!("{} ({}) {}", p.name, p.profile_type, p.description())
                 })
                 .collect();
 
@@ -51,13 +53,23 @@ pub fn run(name: Option<String>) -> Result<()> {
         return Ok(());
     }
 
-    match profile.profile_type {
-        ProfileType::ApiKey => {
-            let _ = keychain::delete_api_key(&name);
-        }
-        ProfileType::OAuth => {
-            let _ = keychain::delete_oauth_token(&name);
+    keychain::delete_profile_secret(&name, &profile.profile_type)?;
+
+    // Undo what `use` wrote for it, putting the user's own settings and
+    // assets back before the linked ones disappear
+    if store.active.as_deref() == Some(name.as_str()) {
+        store.update(use_profile::clear_global)?;
+    }
+
+    // The profile's own Claude Code config directory and assets, if any. A
+    // name stored before names were checked may not be a directory of ours:
+    // leave the filesystem alone then.
+    if profile::validate_name(&name).is_ok() {
+        let dir = isolated::profile_config_dir(&name)?;
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
         }
+        assets::remove_profile_dir(&name)?;
     }
 
     store.remove_profile(&name)?;
@@ -0,0 +1,13 @@
+use crate::error::Result;
+use crate::pin;
+use crate::profile::ProfileStore;
+
+/// Print the profile that applies here; the source goes to stderr so
+/// `$(cswitch resolve)` captures just the name.
+pub fn run() -> Result<()> {
+    let store = ProfileStore::load()?;
+    let resolved = pin::resolve(&store)?;
+    println!("{}", resolved.profile.name);
+    eprintln!("({})", resolved.source);
+    Ok(())
+}
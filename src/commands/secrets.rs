@@ -0,0 +1,93 @@
+use colored::Colorize;
+
+use crate::cli::SecretsCommand;
+use crate::config::Config;
+use crate::error::{CswitchError, Result};
+use crate::profile::ProfileStore;
+use crate::secrets::{self, BackendKind, SecretKind};
+
+pub fn run(command: SecretsCommand) -> Result<()> {
+    match command {
+        SecretsCommand::Status => status(),
+        SecretsCommand::Migrate { to } => migrate(to),
+        SecretsCommand::Passphrase => passphrase(),
+    }
+}
+
+fn status() -> Result<()> {
+    let config = Config::load()?;
+    println!("{} {}", "Secret backend:".bold(), config.secret_backend.to_string().green());
+    Ok(())
+}
+
+/// Copy every secret to the new backend and verify it, switch the config over,
+/// then clear the old backend. Until the config is saved every command keeps
+/// reading from the old backend, so an interrupted migration loses nothing.
+fn migrate(to: BackendKind) -> Result<()> {
+    let mut config = Config::load()?;
+    let from = config.secret_backend;
+    if from == to {
+        println!("Secrets are already stored in '{to}'.");
+        return Ok(());
+    }
+
+    let store = ProfileStore::load()?;
+    let source = secrets::open(from);
+    let target = secrets::open(to);
+
+    let mut names: Vec<_> = store.profiles.keys().collect();
+    names.sort();
+
+    let mut moved = Vec::new();
+    for name in names {
+        for kind in SecretKind::ALL {
+            let Some(secret) = source.get(kind, name)? else {
+                continue;
+            };
+            target.set(kind, name, &secret)?;
+            if target.get(kind, name)?.as_deref() != Some(secret.as_str()) {
+                return Err(CswitchError::Keychain(format!(
+                    "Verification failed for the {kind} of '{name}' in '{to}'. Nothing was switched."
+                )));
+            }
+            println!("  {} {name} ({kind})", "→".blue());
+            moved.push((kind, name));
+        }
+    }
+
+    config.secret_backend = to;
+    config.save()?;
+
+    for (kind, name) in moved.iter() {
+        if let Err(e) = source.delete(*kind, name) {
+            eprintln!(
+                "{} Could not remove the {kind} of '{name}' from '{from}': {e}",
+                "Warning:".yellow().bold()
+            );
+        }
+    }
+
+    println!(
+        "{} Moved {} secret(s) from '{}' to '{}'.",
+        "✓".green().bold(),
+        moved.len(),
+        from,
+        to
+    );
+    Ok(())
+}
+
+fn passphrase() -> Result<()> {
+    let config = Config::load()?;
+    if config.secret_backend != BackendKind::EncryptedFile {
+        return Err(CswitchError::Config(format!(
+            "Secrets are stored in '{}'. Run 'cswitch secrets migrate --to encrypted-file' to encrypt them.",
+            config.secret_backend
+        )));
+    }
+
+    secrets::change_passphrase()?;
+
+    println!("{} Passphrase changed.", "✓".green().bold());
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::claude_config::{self, SettingsChange};
+use crate::cli::SettingsCommand;
+use crate::error::{CswitchError, Result};
+use crate::isolated;
+use crate::profile::{Profile, ProfileStore};
+use crate::settings_overlay::{self, DEFAULT_KEYS, RESERVED_KEYS};
+
+pub fn run(command: SettingsCommand) -> Result<()> {
+    match command {
+        SettingsCommand::Capture { profile, keys } => capture(profile, keys),
+        SettingsCommand::Diff { profile } => diff(profile),
+    }
+}
+
+/// The settings.json a profile's `use` writes to: its own in isolated mode.
+fn target_dir(profile: &Profile) -> Result<Option<std::path::PathBuf>> {
+    if profile.isolated {
+        Ok(Some(isolated::profile_config_dir(&profile.name)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Copy `keys` from the profile's settings.json into its overlay. A key
+/// that isn't set there is dropped from the overlay. A profile that isn't
+/// in use gets the global settings without the active profile's overlay.
+fn capture(name: String, keys: Vec<String>) -> Result<()> {
+    let mut store = ProfileStore::load()?;
+    let profile = store.get_profile(&name)?.clone();
+    let keys = if keys.is_empty() {
+        DEFAULT_KEYS.iter().map(|k| k.to_string()).collect()
+    } else {
+        keys
+    };
+    if let Some(key) = keys.iter().find(|k| RESERVED_KEYS.contains(&k.as_str())) {
+        return Err(CswitchError::Unsupported(format!(
+            "'{key}' is managed by cswitch. Use 'cswitch set-env' for environment variables."
+        )));
+    }
+
+    // When the values come from the overlay's own settings.json, they are
+    // now the overlay's to remove on the next switch
+    let in_place = profile.isolated || store.active.as_deref() == Some(name.as_str());
+    let mut settings = claude_config::read_settings_in(target_dir(&profile)?.as_deref())?;
+    if !in_place {
+        // The global settings.json holds the active profile's overlay; take
+        // the user's own values, not that profile's
+        if let Some(obj) = settings.as_object_mut() {
+            settings_overlay::revert(obj, &store.applied_settings);
+        }
+    }
+    let values: Vec<(&String, Option<&Value>)> = keys.iter().map(|k| (k, settings.get(k))).collect();
+
+    store.update(|store| {
+        let stored = store
+            .profiles
+            .get_mut(&name)
+            .ok_or_else(|| CswitchError::ProfileNotFound(name.clone()))?;
+        for (key, value) in &values {
+            match value {
+                Some(value) => stored.settings.insert(key.to_string(), (*value).clone()),
+                None => stored.settings.shift_remove(key.as_str()),
+            };
+            if profile.isolated {
+                settings_overlay::adopt(&mut stored.isolated_settings, key, *value);
+            }
+        }
+        if in_place && !profile.isolated {
+            for (key, value) in &values {
+                settings_overlay::adopt(&mut store.applied_settings, key, *value);
+            }
+        }
+        Ok(())
+    })?;
+
+    let (set, unset): (Vec<_>, Vec<_>) = values.iter().partition(|(_, v)| v.is_some());
+    let names = |list: &[&(&String, Option<&Value>)]| list.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>().join(", ");
+    if set.is_empty() {
+        println!("{} None of {} set; removed them from '{}'.", "✓".green().bold(), names(&unset), name);
+    } else {
+        println!("{} Captured {} for '{}'.", "✓".green().bold(), names(&set), name);
+        if !unset.is_empty() {
+            println!("  Not set, so removed from the profile: {}", names(&unset));
+        }
+    }
+    if !in_place {
+        println!("  They apply on 'cswitch use {name}'.");
+    }
+    Ok(())
+}
+
+/// Show what `cswitch use <name>` would change in settings.json.
+fn diff(name: String) -> Result<()> {
+    let store = ProfileStore::load()?;
+    let profile = store.get_profile(&name)?;
+    let dir = target_dir(profile)?;
+
+    let (remove_env, revert) = if profile.isolated {
+        (&profile.isolated_env, &profile.isolated_settings)
+    } else {
+        (&store.managed_env, &store.applied_settings)
+    };
+    let env = profile.settings_env();
+
+    let before = claude_config::read_settings_in(dir.as_deref())?;
+    let mut after = before.clone();
+    if let Some(obj) = after.as_object_mut() {
+        SettingsChange {
+            helper: profile.profile_type.uses_key_helper(),
+            remove_env,
+            set_env: &env,
+            revert,
+            overlay: &profile.settings,
+        }
+        .apply_to(obj);
+    }
+
+    let path = match &dir {
+        Some(dir) => dir.join(claude_config::SETTINGS_FILE),
+        None => claude_config::settings_path()?,
+    };
+    let changes = settings_overlay::diff(&before, &after);
+    if changes.is_empty() {
+        println!("{} already matches '{}'.", path.display(), name);
+        return Ok(());
+    }
+
+    println!("{} {}", "cswitch use".bold(), name);
+    println!("{}", path.display().to_string().dimmed());
+    for change in changes {
+        let show = |v: &Value| v.to_string();
+        let line = match (&change.before, &change.after) {
+            (None, Some(after)) => format!("+ {}: {}", change.path, show(after)).green(),
+            (Some(before), None) => format!("- {}: {}", change.path, show(before)).red(),
+            (Some(before), Some(after)) => {
+                format!("~ {}: {} → {}", change.path, show(before), show(after)).yellow()
+            }
+            (None, None) => continue,
+        };
+        println!("{line}");
+    }
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use colored::Colorize;
 use dialoguer::Select;
+use std::collections::BTreeMap;
 
-use crate::claude_config;
+use crate::assets;
+use crate::claude_config::{self, SettingsChange};
 use crate::error::{CswitchError, Result};
+use crate::isolated;
 use crate::keychain;
-use crate::profile::{ProfileStore, ProfileType};
+use crate::live;
+use crate::profile::{Profile, ProfileStore, ProfileType};
 
 pub fn run(name: Option<String>) -> Result<()> {
     let mut store = ProfileStore::load()?;
@@ -29,8 +33,7 @@ pub fn run(name: Option<String>) -> Result<()> {
                     } else {
                         "  "
                     };
-                    let label = p.label.as_deref().unwrap_or("");
-                    format!("{}{} ({}) {}", active, p.name, p.profile_type, label)
+                    format!("{}{} ({}) {}", active, p.name, p.profile_type, p.description())
                 })
                 .collect();
 
@@ -60,25 +63,125 @@ pub fn run(name: Option<String>) -> Result<()> {
         );
     }
 
+    switch(&mut store, &profile)?;
+
+    println!(
+        "{} Switched to '{}' ({}).",
+        "✓".green().bold(),
+        name,
+        profile.profile_type
+    );
+    if profile.isolated && isolated::current_profile().as_deref() != Some(name.as_str()) {
+        println!(
+            "  Isolated: Claude Code uses it where CLAUDE_CONFIG_DIR={}.\n  The shell hook sets that for you; otherwise run: eval \"$(cswitch env {name})\"",
+            isolated::profile_config_dir(&name)?.display()
+        );
+    }
+    Ok(())
+}
+
+/// Point Claude Code at `profile` and mark it active: credentials (and the
+/// account recorded with OAuth ones), then the profile's settings env
+/// (provider variables and its `env` block), settings overlay and assets in
+/// place of the previous one's. An isolated profile gets all of this in its
+/// own directory instead; the global config only loses the previous
+/// profile's helper, env, overlay and assets, so a session there doesn't ask
+/// `emit-key` for the isolated profile's key. The OAuth token is only
+/// refreshed when it is about to expire.
+pub fn switch(store: &mut ProfileStore, profile: &Profile) -> Result<()> {
+    if let Some(warning) = profile.expiry_warning() {
+        eprintln!("{} {warning}", "Warning:".yellow().bold());
+    }
+
+    // Before anything replaces Claude Code's login, keep what it refreshed
+    if let Err(e) = live::sync_back(store) {
+        eprintln!(
+            "{} Could not save Claude Code's current credentials: {e}",
+            "Warning:".yellow().bold()
+        );
+    }
+
+    if profile.isolated {
+        isolated::prepare(profile)?;
+        return store.update(|store| {
+            clear_global(store)?;
+            store.mark_active(&profile.name)
+        });
+    }
+
     match profile.profile_type {
         ProfileType::ApiKey => {
-            let _ = keychain::get_api_key(&name)?;
-            claude_config::enable_api_key_helper()?;
+            let _ = keychain::get_raw_api_key(&profile.name)?;
+        }
+        ProfileType::Gateway { .. } => {
+            let _ = keychain::get_raw_auth_token(&profile.name)?;
         }
         ProfileType::OAuth => {
-            let token = keychain::refresh_oauth_token_if_needed(&name)?;
+            let token = keychain::refresh_oauth_token_if_needed(&profile.name)?;
             keychain::set_claude_credentials(&token)?;
-            claude_config::disable_api_key_helper()?;
+            store.refresh_account(&profile.name, &token)?;
+            // The credentials are in place; a stale account name is not
+            // worth failing the switch over
+            if let Some(account) = &profile.oauth_account {
+                if let Err(e) = claude_config::write_oauth_account(None, account) {
+                    eprintln!(
+                        "{} Could not update the account in ~/.claude.json: {e}",
+                        "Warning:".yellow().bold()
+                    );
+                }
+            }
         }
+        // Claude Code's credential store rather than settings.json, which
+        // may be shared or committed
+        ProfileType::SetupToken { expires_at } => {
+            let token = keychain::get_setup_token(&profile.name)?;
+            let credentials = keychain::setup_token_credentials(&token, expires_at);
+            keychain::set_claude_credentials(&credentials)?;
+        }
+        // Claude Code authenticates to the cloud provider itself
+        ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => {}
     }
 
-    store.set_active(&name)?;
+    let env = profile.settings_env();
+    store.update(|store| {
+        apply_global(
+            store,
+            profile.profile_type.uses_key_helper(),
+            &env,
+            &profile.settings,
+            Some(&profile.name),
+        )?;
+        store.mark_active(&profile.name)
+    })
+}
+
+/// Take the active profile's helper, env, overlay and assets out of the
+/// global config, putting back what they replaced.
+pub fn clear_global(store: &mut ProfileStore) -> Result<()> {
+    apply_global(store, false, &BTreeMap::new(), &serde_json::Map::new(), None)
+}
 
-    println!(
-        "{} Switched to '{}' ({}).",
-        "✓".green().bold(),
-        name,
-        profile.profile_type
-    );
+/// Replace the previous profile's helper, env, overlay and assets in the
+/// global config with these, and record what was written.
+fn apply_global(
+    store: &mut ProfileStore,
+    helper: bool,
+    env: &BTreeMap<String, String>,
+    overlay: &serde_json::Map<String, serde_json::Value>,
+    assets_of: Option<&str>,
+) -> Result<()> {
+    let applied = claude_config::apply_change(
+        None,
+        &SettingsChange {
+            helper,
+            remove_env: &store.managed_env,
+            set_env: env,
+            revert: &store.applied_settings,
+            overlay,
+        },
+    )?;
+    store.managed_env = applied.env;
+    store.applied_settings = applied.settings;
+    store.linked_assets = assets::apply(&claude_config::claude_dir()?, &store.linked_assets, assets_of)?;
     Ok(())
 }
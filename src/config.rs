@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::secrets::BackendKind;
+
+/// Per-install settings, stored in `~/.config/cswitch/config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Where profile secrets are stored.
+    #[serde(default)]
+    pub secret_backend: BackendKind,
+
+    /// How long a `cmd:` secret reference may run before it is killed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_command_timeout_secs: Option<u64>,
+
+    /// OAuth authorize page, when not Claude's (`CSWITCH_OAUTH_AUTHORIZE_URL` wins).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth_authorize_url: Option<String>,
+
+    /// OAuth token endpoint, when not Claude's (`CSWITCH_OAUTH_TOKEN_URL` wins).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth_token_url: Option<String>,
+
+    /// OAuth client id, when not Claude Code's (`CSWITCH_OAUTH_CLIENT_ID` wins).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth_client_id: Option<String>,
+
+    /// Directory → profile rules, used when no `.cswitch` pin applies.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directory_rules: Vec<DirectoryRule>,
+}
+
+/// Use `profile` anywhere under `path` (`~/` is expanded).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryRule {
+    pub path: String,
+    pub profile: String,
+}
+
+impl DirectoryRule {
+    pub fn expanded_path(&self) -> Option<PathBuf> {
+        match self.path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+            None => Some(PathBuf::from(&self.path)),
+        }
+    }
+}
+
+impl Config {
+    pub fn config_path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| CswitchError::Config("Cannot determine config directory".into()))?;
+        Ok(config_dir.join("cswitch").join("config.json"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::config_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path)?;
+        let config: Self = serde_json::from_str(&data)
+            .map_err(|e| CswitchError::Config(format!("Invalid {}: {e}", path.display())))?;
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::config_path()?;
+        let data = serde_json::to_string_pretty(self)?;
+        fsutil::write_atomic(&path, data.as_bytes(), 0o644)?;
+        Ok(())
+    }
+}
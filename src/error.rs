@@ -5,15 +5,40 @@ pub enum CswitchError {
     #[error("Profile '{0}' not found")]
     ProfileNotFound(String),
 
+    #[error("Profile '{name}' ({origin}) not found")]
+    PinnedProfileNotFound { name: String, origin: String },
+
     #[error("Profile '{0}' already exists")]
     ProfileAlreadyExists(String),
 
+    #[error("Invalid profile name '{0}': use a plain name, without '/' or '..'")]
+    InvalidProfileName(String),
+
     #[error("No active profile set")]
     NoActiveProfile,
 
+    #[error("{0}")]
+    Unsupported(String),
+
     #[error("Keychain error: {0}")]
     Keychain(String),
 
+    #[error("Wrong passphrase for encrypted credentials")]
+    WrongPassphrase,
+
+    #[error("Encrypted credentials file is corrupted: {0}")]
+    CorruptedCredentials(String),
+
+    #[error("Cannot resolve secret reference: {0}")]
+    SecretReference(String),
+
+    #[error("{file} was written by newer cswitch v{written_by} (format {version}). Upgrade cswitch to use it.")]
+    NewerSchema {
+        file: String,
+        version: u64,
+        written_by: String,
+    },
+
     #[error("Failed to read/write config: {0}")]
     Config(String),
 
@@ -30,8 +55,18 @@ pub enum CswitchError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("{0}")]
+    Exec(String),
+
+    #[error("{0} doctor check(s) failed")]
+    DoctorFailed(usize),
+
     #[error("OAuth error: {0}")]
     OAuth(String),
+
+    /// The token endpoint answered with a 4xx: retrying won't help.
+    #[error("OAuth error: token request refused: {0}")]
+    OAuthRejected(String),
 }
 
 pub type Result<T> = std::result::Result<T, CswitchError>;
@@ -0,0 +1,111 @@
+// Crash- and race-safe file helpers for the stores cswitch shares between
+// concurrent invocations (`emit-key` from Claude Code, `use` in a terminal...).
+//
+// Writers hold an advisory lock on a `<file>.lock` sibling for the whole
+// read-modify-write cycle and replace the file with a rename, so readers never
+// need the lock: they always see either the old or the new complete file.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Lock files this process currently holds, so nested `lock` calls on the same
+/// file don't deadlock against ourselves.
+static HELD: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// An exclusive advisory lock, released when the outermost guard is dropped.
+pub struct FileLock {
+    held: Option<(PathBuf, File)>,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Some((path, _file)) = self.held.take() {
+            if let Ok(mut held) = HELD.lock() {
+                held.retain(|p| p != &path);
+            }
+        }
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("data");
+    path.with_file_name(format!("{file_name}.lock"))
+}
+
+/// Block until this process holds the write lock for `path`. Re-entrant.
+pub fn lock(path: &Path) -> std::io::Result<FileLock> {
+    let lock_path = lock_path(path);
+    if HELD.lock().map(|h| h.contains(&lock_path)).unwrap_or(false) {
+        return Ok(FileLock { held: None });
+    }
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&lock_path)?;
+    file.lock()?;
+    if let Ok(mut held) = HELD.lock() {
+        held.push(lock_path.clone());
+    }
+    Ok(FileLock { held: Some((lock_path, file)) })
+}
+
+/// Write `data` to a sibling temp file, fsync it, then rename it over `path`.
+/// An existing file keeps its permissions; a new one gets `mode`.
+pub fn write_atomic(path: &Path, data: &[u8], mode: u32) -> std::io::Result<()> {
+    let mode = fs::metadata(path)
+        .map(|m| m.permissions().mode() & 0o777)
+        .unwrap_or(mode);
+    replace(path, data, mode)
+}
+
+/// Like [`write_atomic`], but always owner-only (600).
+pub fn write_private_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    replace(path, data, 0o600)
+}
+
+/// Where a write to `path` lands: the file a symlink there points to, so a
+/// link into a dotfiles repo stays a link. Other paths are used as they are.
+fn write_target(path: &Path) -> std::io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::canonicalize(path),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+fn replace(path: &Path, data: &[u8], mode: u32) -> std::io::Result<()> {
+    let path = &write_target(path)?;
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("data");
+    let tmp = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .open(&tmp)?;
+        // `mode` is filtered by the umask on create; apply it exactly
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        // Persist the rename itself
+        File::open(parent)?.sync_all()
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
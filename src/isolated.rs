@@ -0,0 +1,149 @@
+// Per-profile Claude Code config directories under cswitch's data dir, used
+// through `CLAUDE_CONFIG_DIR` so a process can run as a profile without
+// touching the global Claude Code login. `exec` uses them for OAuth profiles;
+// profiles in isolated mode use them for everything.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+use crate::claude_config::{self, SettingsChange};
+use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::keychain;
+use crate::profile::{self, Profile, ProfileStore, ProfileType};
+
+const CREDENTIALS_FILE: &str = ".credentials.json";
+
+/// `<data dir>/cswitch/profiles`
+fn profiles_root() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| CswitchError::Config("Cannot determine data directory".into()))?;
+    Ok(data_dir.join("cswitch").join("profiles"))
+}
+
+/// `<data dir>/cswitch/profiles/<name>`
+pub fn profile_config_dir(profile_name: &str) -> Result<PathBuf> {
+    profile::validate_name(profile_name)?;
+    Ok(profiles_root()?.join(profile_name))
+}
+
+/// The profile whose config directory `dir` is, if it is one.
+pub fn profile_for_dir(dir: &Path) -> Option<String> {
+    let root = profiles_root().ok()?;
+    if dir.parent()? != root {
+        return None;
+    }
+    dir.file_name()?.to_str().map(str::to_string)
+}
+
+/// The profile whose config directory `CLAUDE_CONFIG_DIR` points at.
+pub fn current_profile() -> Option<String> {
+    let dir = std::env::var_os("CLAUDE_CONFIG_DIR")?;
+    profile_for_dir(Path::new(&dir))
+}
+
+/// Keep the newer of the stored token and the one in the profile's directory.
+/// Claude Code refreshes (and may rotate) the token in place while it runs.
+fn sync_back(profile_name: &str, path: &Path) -> Result<()> {
+    if let Ok(live) = fs::read_to_string(path) {
+        keychain::store_oauth_token_if_newer(profile_name, &live)?;
+    }
+    Ok(())
+}
+
+/// Write the profile's current OAuth credentials into `dir`.
+fn write_oauth_credentials(profile_name: &str, dir: &Path) -> Result<()> {
+    let path = dir.join(CREDENTIALS_FILE);
+    let _lock = fsutil::lock(&path)?;
+    sync_back(profile_name, &path)?;
+
+    let token = keychain::refresh_oauth_token_if_needed(profile_name)?;
+    fsutil::write_private_atomic(&path, token.as_bytes())?;
+    ProfileStore::load()?.refresh_account(profile_name, &token)?;
+    Ok(())
+}
+
+/// Start a new directory from the global `.claude.json` (onboarding, theme,
+/// trusted projects) without the account it was logged in with.
+fn seed_claude_json(dir: &Path) -> Result<()> {
+    let path = dir.join(claude_config::CLAUDE_JSON_FILE);
+    if path.exists() {
+        return Ok(());
+    }
+    let Ok(data) = fs::read_to_string(claude_config::claude_json_path()?) else {
+        return Ok(());
+    };
+    let Ok(mut state) = serde_json::from_str::<serde_json::Value>(&data) else {
+        return Ok(());
+    };
+    if let Some(obj) = state.as_object_mut() {
+        obj.shift_remove("oauthAccount");
+    }
+    let data = serde_json::to_string_pretty(&state)?;
+    fsutil::write_private_atomic(&path, data.as_bytes())?;
+    Ok(())
+}
+
+/// Create the profile's config directory and write its credentials, then
+/// return it. The directory is seeded from the global config on first use
+/// and gets the account recorded with an OAuth login in `.claude.json`. For
+/// an isolated profile its settings.json also gets the profile's
+/// `apiKeyHelper`, env and settings overlay, and its assets are linked in.
+pub fn prepare(profile: &Profile) -> Result<PathBuf> {
+    let dir = profile_config_dir(&profile.name)?;
+    fs::create_dir_all(&dir)?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+
+    match profile.profile_type {
+        ProfileType::OAuth => write_oauth_credentials(&profile.name, &dir)?,
+        ProfileType::ApiKey if profile.isolated => {
+            let _ = keychain::get_raw_api_key(&profile.name)?;
+        }
+        ProfileType::Gateway { .. } if profile.isolated => {
+            let _ = keychain::get_raw_auth_token(&profile.name)?;
+        }
+        ProfileType::SetupToken { expires_at } if profile.isolated => {
+            let token = keychain::get_setup_token(&profile.name)?;
+            let credentials = keychain::setup_token_credentials(&token, expires_at);
+            fsutil::write_private_atomic(&dir.join(CREDENTIALS_FILE), credentials.as_bytes())?;
+        }
+        _ => {}
+    }
+
+    seed_claude_json(&dir)?;
+    if let (ProfileType::OAuth, Some(account)) = (&profile.profile_type, &profile.oauth_account) {
+        claude_config::write_oauth_account(Some(&dir), account)?;
+    }
+    let mut store = ProfileStore::load()?;
+    claude_config::seed_settings(&dir, &store.managed_env, &store.applied_settings)?;
+    if !profile.isolated {
+        return Ok(dir);
+    }
+
+    let env = profile.settings_env();
+    let applied = claude_config::apply_change(
+        Some(&dir),
+        &SettingsChange {
+            helper: profile.profile_type.uses_key_helper(),
+            remove_env: &profile.isolated_env,
+            set_env: &env,
+            revert: &profile.isolated_settings,
+            overlay: &profile.settings,
+        },
+    )?;
+
+    assets::link_into(&dir, &profile.name)?;
+
+    if applied.env != profile.isolated_env || applied.settings != profile.isolated_settings {
+        store.update(|store| {
+            if let Some(stored) = store.profiles.get_mut(&profile.name) {
+                stored.isolated_env = applied.env;
+                stored.isolated_settings = applied.settings;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(dir)
+}
@@ -1,52 +1,27 @@
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
 use serde_json::Value;
-use std::collections::HashMap;
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::claude_config;
 use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::profile::ProfileType;
+use crate::secrets::{self, reference, SecretKind};
+use crate::token_endpoint;
 
-const OAUTH_TOKEN_ENDPOINT: &str = "https://console.anthropic.com/v1/oauth/token";
-const OAUTH_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+/// Refresh tokens this close to expiry (5 minutes).
+const REFRESH_MARGIN_MS: i64 = 300_000;
 
 const CLAUDE_KEYCHAIN_SERVICE: &str = "Claude Code-credentials";
+const CLAUDE_CREDENTIALS_FILE: &str = ".credentials.json";
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct CredentialStore {
-    api_keys: HashMap<String, String>,
-    oauth_tokens: HashMap<String, String>,
-}
-
-fn credentials_path() -> Result<PathBuf> {
+fn refresh_lock_path() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
         .ok_or_else(|| CswitchError::Keychain("Cannot determine config directory".into()))?;
-    Ok(config_dir.join("cswitch").join("credentials.json"))
-}
-
-fn load_store() -> Result<CredentialStore> {
-    let path = credentials_path()?;
-    if !path.exists() {
-        return Ok(CredentialStore::default());
-    }
-    let data = fs::read_to_string(&path)
-        .map_err(|e| CswitchError::Keychain(format!("Failed to read credentials: {e}")))?;
-    serde_json::from_str(&data).map_err(|e| CswitchError::Keychain(format!("Invalid credentials file: {e}")))
-}
-
-fn save_store(store: &CredentialStore) -> Result<()> {
-    let path = credentials_path()?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    let data = serde_json::to_string_pretty(store)
-        .map_err(|e| CswitchError::Keychain(format!("Failed to serialize credentials: {e}")))?;
-    fs::write(&path, &data)
-        .map_err(|e| CswitchError::Keychain(format!("Failed to write credentials: {e}")))?;
-    // Restrict permissions to owner only (600)
-    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
-    Ok(())
+    Ok(config_dir.join("cswitch").join("oauth-refresh"))
 }
 
 fn claude_user() -> String {
@@ -58,46 +33,142 @@ fn claude_user() -> String {
 // --- API keys ---
 
 pub fn set_api_key(profile_name: &str, api_key: &str) -> Result<()> {
-    let mut store = load_store()?;
-    store.api_keys.insert(profile_name.to_string(), api_key.to_string());
-    save_store(&store)
+    secrets::active()?.set(SecretKind::ApiKey, profile_name, api_key)
 }
 
+/// Return the API key, resolving `env:`/`file:`/`cmd:` references.
 pub fn get_api_key(profile_name: &str) -> Result<String> {
-    let store = load_store()?;
-    store.api_keys.get(profile_name).cloned()
-        .ok_or_else(|| CswitchError::Keychain(format!("No API key found for profile '{profile_name}'")))
+    reference::resolve(&get_raw_api_key(profile_name)?)
+}
+
+/// Return the API key as stored: a literal key or an unresolved reference.
+pub fn get_raw_api_key(profile_name: &str) -> Result<String> {
+    get_secret(SecretKind::ApiKey, profile_name)
 }
 
 pub fn delete_api_key(profile_name: &str) -> Result<()> {
-    let mut store = load_store()?;
-    store.api_keys.remove(profile_name);
-    save_store(&store)
+    secrets::active()?.delete(SecretKind::ApiKey, profile_name)
 }
 
 // --- OAuth tokens ---
 
 pub fn set_oauth_token(profile_name: &str, token_json: &str) -> Result<()> {
-    let mut store = load_store()?;
-    store.oauth_tokens.insert(profile_name.to_string(), token_json.to_string());
-    save_store(&store)
+    secrets::active()?.set(SecretKind::OAuthToken, profile_name, token_json)
 }
 
 pub fn get_oauth_token(profile_name: &str) -> Result<String> {
-    let store = load_store()?;
-    store.oauth_tokens.get(profile_name).cloned()
-        .ok_or_else(|| CswitchError::Keychain(format!("No OAuth token found for profile '{profile_name}'")))
+    get_secret(SecretKind::OAuthToken, profile_name)
 }
 
 pub fn delete_oauth_token(profile_name: &str) -> Result<()> {
-    let mut store = load_store()?;
-    store.oauth_tokens.remove(profile_name);
-    save_store(&store)
+    secrets::active()?.delete(SecretKind::OAuthToken, profile_name)
+}
+
+// --- Setup tokens ---
+//
+// The raw `sk-ant-oat…` token of a setup-token profile.
+
+pub fn set_setup_token(profile_name: &str, token: &str) -> Result<()> {
+    secrets::active()?.set(SecretKind::SetupToken, profile_name, token)
+}
+
+pub fn get_setup_token(profile_name: &str) -> Result<String> {
+    get_secret(SecretKind::SetupToken, profile_name)
+}
+
+pub fn delete_setup_token(profile_name: &str) -> Result<()> {
+    secrets::active()?.delete(SecretKind::SetupToken, profile_name)
+}
+
+/// Claude Code credentials JSON holding a setup token: an access token
+/// without a refresh token, used as-is until it expires.
+pub fn setup_token_credentials(token: &str, expires_at: Option<DateTime<Utc>>) -> String {
+    serde_json::json!({
+        "claudeAiOauth": {
+            "accessToken": token,
+            "refreshToken": null,
+            "expiresAt": expires_at.map(|at| at.timestamp_millis()),
+            "scopes": ["user:inference"],
+        }
+    })
+    .to_string()
+}
+
+// --- Gateway bearer tokens ---
+
+pub fn set_auth_token(profile_name: &str, token: &str) -> Result<()> {
+    secrets::active()?.set(SecretKind::AuthToken, profile_name, token)
+}
+
+/// Return the bearer token, resolving `env:`/`file:`/`cmd:` references, so a
+/// `cmd:` token is fetched fresh on every call.
+pub fn get_auth_token(profile_name: &str) -> Result<String> {
+    reference::resolve(&get_raw_auth_token(profile_name)?)
+}
+
+/// Return the bearer token as stored: a literal token or an unresolved reference.
+pub fn get_raw_auth_token(profile_name: &str) -> Result<String> {
+    get_secret(SecretKind::AuthToken, profile_name)
+}
+
+pub fn delete_auth_token(profile_name: &str) -> Result<()> {
+    secrets::active()?.delete(SecretKind::AuthToken, profile_name)
 }
 
-// --- Claude Code credentials (via macOS `security` CLI, no dialog) ---
+// --- Per profile type ---
+
+/// The kind of secret a profile of this type keeps, if any.
+pub fn secret_kind(profile_type: &ProfileType) -> Option<SecretKind> {
+    match profile_type {
+        ProfileType::ApiKey => Some(SecretKind::ApiKey),
+        ProfileType::OAuth => Some(SecretKind::OAuthToken),
+        ProfileType::Gateway { .. } => Some(SecretKind::AuthToken),
+        ProfileType::SetupToken { .. } => Some(SecretKind::SetupToken),
+        ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => None,
+    }
+}
+
+/// Delete the secret a profile of type `profile_type` keeps, if any.
+pub fn delete_profile_secret(profile_name: &str, profile_type: &ProfileType) -> Result<()> {
+    match profile_type {
+        ProfileType::ApiKey => delete_api_key(profile_name),
+        ProfileType::OAuth => delete_oauth_token(profile_name),
+        ProfileType::Gateway { .. } => delete_auth_token(profile_name),
+        ProfileType::SetupToken { .. } => delete_setup_token(profile_name),
+        ProfileType::Bedrock { .. } | ProfileType::Vertex { .. } => Ok(()),
+    }
+}
+
+fn get_secret(kind: SecretKind, profile_name: &str) -> Result<String> {
+    secrets::active()?.get(kind, profile_name)?.ok_or_else(|| {
+        CswitchError::Keychain(format!("No {kind} found for profile '{profile_name}'"))
+    })
+}
+
+// --- Claude Code credentials ---
+//
+// On macOS Claude Code keeps its login in the Keychain; elsewhere it uses
+// `.credentials.json` inside its config directory.
 
 pub fn get_claude_credentials() -> Result<String> {
+    if cfg!(target_os = "macos") {
+        get_keychain_credentials()
+    } else {
+        get_file_credentials()
+    }
+}
+
+pub fn set_claude_credentials(token_json: &str) -> Result<()> {
+    if cfg!(target_os = "macos") {
+        set_keychain_credentials(token_json)
+    } else {
+        set_file_credentials(token_json)
+    }
+}
+
+// macOS: `security` CLI, no dialog
+
+fn get_keychain_credentials() -> Result<String> {
     let output = std::process::Command::new("security")
         .args(["find-generic-password", "-s", CLAUDE_KEYCHAIN_SERVICE, "-a", &claude_user(), "-w"])
         .output()
@@ -112,7 +183,7 @@ pub fn get_claude_credentials() -> Result<String> {
         .map_err(|e| CswitchError::Keychain(format!("Invalid credentials encoding: {e}")))
 }
 
-pub fn set_claude_credentials(token_json: &str) -> Result<()> {
+fn set_keychain_credentials(token_json: &str) -> Result<()> {
     // Delete existing entry first (security add fails if it exists)
     let _ = std::process::Command::new("security")
         .args(["delete-generic-password", "-s", CLAUDE_KEYCHAIN_SERVICE, "-a", &claude_user()])
@@ -129,11 +200,144 @@ pub fn set_claude_credentials(token_json: &str) -> Result<()> {
     Ok(())
 }
 
+// Linux and others: `<claude dir>/.credentials.json`
+
+fn claude_credentials_path() -> Result<PathBuf> {
+    Ok(claude_config::claude_dir()?.join(CLAUDE_CREDENTIALS_FILE))
+}
+
+/// The file Claude Code keeps its login in, or `None` where it uses the Keychain.
+pub fn claude_credentials_file() -> Result<Option<PathBuf>> {
+    if cfg!(target_os = "macos") {
+        Ok(None)
+    } else {
+        claude_credentials_path().map(Some)
+    }
+}
+
+fn get_file_credentials() -> Result<String> {
+    let path = claude_credentials_path()?;
+    if !path.exists() {
+        return Err(CswitchError::Keychain(format!(
+            "No Claude Code credentials found in {}",
+            path.display()
+        )));
+    }
+    let data = fs::read_to_string(&path)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to read {}: {e}", path.display())))?;
+    Ok(data.trim().to_string())
+}
+
+fn set_file_credentials(token_json: &str) -> Result<()> {
+    let path = claude_credentials_path()?;
+    let _lock = fsutil::lock(&path)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to lock {}: {e}", path.display())))?;
+
+    let incoming: Value = serde_json::from_str(token_json)
+        .map_err(|e| CswitchError::Keychain(format!("Invalid token JSON: {e}")))?;
+    let incoming = incoming
+        .as_object()
+        .ok_or_else(|| CswitchError::Keychain("Token JSON is not an object".into()))?;
+
+    // Merge into the existing file so unrelated keys (e.g. MCP OAuth) survive
+    let mut creds = if path.exists() {
+        let data = fs::read_to_string(&path)
+            .map_err(|e| CswitchError::Keychain(format!("Failed to read {}: {e}", path.display())))?;
+        match serde_json::from_str::<Value>(&data) {
+            Ok(Value::Object(map)) => map,
+            _ if data.trim().is_empty() => serde_json::Map::new(),
+            // Not ours to discard: keep a copy before starting over
+            _ => {
+                let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("credentials");
+                let backup = path.with_file_name(format!("{file_name}.{secs}.bak"));
+                fsutil::write_private_atomic(&backup, data.as_bytes()).map_err(|e| {
+                    CswitchError::Keychain(format!("Failed to back up {}: {e}", path.display()))
+                })?;
+                eprintln!(
+                    "{} {} was not a JSON object; it was saved as {} and replaced.",
+                    "Warning:".yellow().bold(),
+                    path.display(),
+                    backup.display()
+                );
+                serde_json::Map::new()
+            }
+        }
+    } else {
+        serde_json::Map::new()
+    };
+    for (key, value) in incoming {
+        creds.insert(key.clone(), value.clone());
+    }
+
+    let data = serde_json::to_string_pretty(&Value::Object(creds))
+        .map_err(|e| CswitchError::Keychain(format!("Failed to serialize credentials: {e}")))?;
+    fsutil::write_private_atomic(&path, data.as_bytes())
+        .map_err(|e| CswitchError::Keychain(format!("Failed to write {}: {e}", path.display())))
+}
+
 // --- OAuth token refresh ---
 
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// Expiry (ms since epoch) and whether a refresh token is present, for a
+/// stored `claudeAiOauth` token JSON.
+pub fn oauth_token_expiry(token_json: &str) -> Option<(i64, bool)> {
+    let creds: Value = serde_json::from_str(token_json).ok()?;
+    let oauth = creds.get("claudeAiOauth")?;
+    let expires_at = oauth.get("expiresAt")?.as_i64()?;
+    let has_refresh = oauth
+        .get("refreshToken")
+        .and_then(|v| v.as_str())
+        .is_some_and(|t| !t.is_empty());
+    Some((expires_at, has_refresh))
+}
+
+/// Whether a token expiring at `expires_at` is within the refresh window.
+pub fn oauth_token_expiring(expires_at: i64) -> bool {
+    now_ms() >= expires_at - REFRESH_MARGIN_MS
+}
+
+/// Store `token_json` for `profile_name` if it expires later than the stored
+/// token, or that one is unreadable. Claude Code refreshes (and may rotate)
+/// a token it is using; this is how cswitch catches up. Returns whether it
+/// was stored.
+pub fn store_oauth_token_if_newer(profile_name: &str, token_json: &str) -> Result<bool> {
+    let Some((expires_at, _)) = oauth_token_expiry(token_json) else {
+        return Ok(false);
+    };
+    // Under the refresh lock, so a concurrent refresh can't be overwritten
+    let _lock = fsutil::lock(&refresh_lock_path()?)?;
+    let stored_expiry = get_oauth_token(profile_name)
+        .ok()
+        .and_then(|t| oauth_token_expiry(&t))
+        .map(|(expires_at, _)| expires_at);
+    if stored_expiry.is_some_and(|stored| stored >= expires_at) {
+        return Ok(false);
+    }
+    set_oauth_token(profile_name, token_json.trim())?;
+    Ok(true)
+}
+
 /// Check if the stored OAuth token is expired and refresh it if needed.
 /// Returns the (possibly refreshed) token JSON string ready to write to Keychain.
 pub fn refresh_oauth_token_if_needed(profile_name: &str) -> Result<String> {
+    // Fast path: a valid token needs no lock and no network
+    let token_json = get_oauth_token(profile_name)?;
+    if let Some((expires_at, _)) = oauth_token_expiry(&token_json) {
+        if !oauth_token_expiring(expires_at) {
+            return Ok(token_json);
+        }
+    }
+
+    // Serialize refreshes: a rotated refresh token is single-use, so a second
+    // process must see the first one's result instead of refreshing again.
+    let _lock = fsutil::lock(&refresh_lock_path()?)?;
     let token_json = get_oauth_token(profile_name)?;
 
     let mut creds: Value = serde_json::from_str(&token_json)
@@ -158,13 +362,10 @@ pub fn refresh_oauth_token_if_needed(profile_name: &str) -> Result<String> {
         (expires_at, refresh_token)
     };
 
-    let now_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as i64;
+    let now_ms = now_ms();
 
     // Still valid (with 5-minute buffer) → return as-is
-    if now_ms < expires_at - 300_000 {
+    if !oauth_token_expiring(expires_at) {
         return Ok(token_json);
     }
 
@@ -176,67 +377,26 @@ pub fn refresh_oauth_token_if_needed(profile_name: &str) -> Result<String> {
 
     eprintln!("  Token expired, refreshing…");
 
-    let body = format!(
-        "grant_type=refresh_token&client_id={OAUTH_CLIENT_ID}&refresh_token={refresh_token}"
-    );
-
-    let output = std::process::Command::new("curl")
-        .args([
-            "-s",
-            "-X",
-            "POST",
-            OAUTH_TOKEN_ENDPOINT,
-            "-H",
-            "Content-Type: application/x-www-form-urlencoded",
-            "-d",
-            &body,
-        ])
-        .output()
-        .map_err(|e| CswitchError::OAuth(format!("Failed to call refresh endpoint: {e}")))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(CswitchError::OAuth(format!(
-            "Token refresh request failed: {stderr}. Re-authenticate with 'cswitch add --oauth'."
-        )));
-    }
-
-    let response: Value = serde_json::from_slice(&output.stdout).map_err(|e| {
-        CswitchError::OAuth(format!(
-            "Invalid refresh response: {e}. Re-authenticate with 'cswitch add --oauth'."
-        ))
+    let response = token_endpoint::refresh(&refresh_token).map_err(|e| match e {
+        CswitchError::OAuthRejected(msg) => CswitchError::OAuth(format!(
+            "Token refresh refused ({msg}). Re-authenticate with 'cswitch add {profile_name}'."
+        )),
+        other => other,
     })?;
 
-    // Check for error in response body
-    if let Some(error) = response.get("error") {
-        return Err(CswitchError::OAuth(format!(
-            "Token refresh failed: {}. Re-authenticate with 'cswitch add --oauth'.",
-            error.as_str().unwrap_or("unknown error")
-        )));
-    }
-
-    let new_access_token = response
-        .get("access_token")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| CswitchError::OAuth("No access_token in refresh response".into()))?;
-
-    let new_refresh_token = response
-        .get("refresh_token")
-        .and_then(|v| v.as_str())
-        .unwrap_or(&refresh_token);
-
-    let expires_in = response
-        .get("expires_in")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(3600);
-
+    let new_refresh_token = response.refresh_token.as_deref().unwrap_or(&refresh_token);
+    let expires_in = response.expires_in.unwrap_or(3600);
     let new_expires_at = now_ms + (expires_in * 1000);
 
-    // Update token in-place, preserving scopes/subscriptionType/rateLimitTier
+    // Update token in-place, preserving subscriptionType/rateLimitTier
     if let Some(oauth_mut) = creds.get_mut("claudeAiOauth") {
-        oauth_mut["accessToken"] = Value::String(new_access_token.to_string());
+        oauth_mut["accessToken"] = Value::String(response.access_token.clone());
         oauth_mut["refreshToken"] = Value::String(new_refresh_token.to_string());
         oauth_mut["expiresAt"] = Value::Number(serde_json::Number::from(new_expires_at));
+        // The grant can narrow on refresh; keep what the server says it is
+        if let Some(scope) = &response.scope {
+            oauth_mut["scopes"] = scope.split_whitespace().map(|s| Value::String(s.to_string())).collect();
+        }
     }
 
     let new_token_json = serde_json::to_string(&creds)
@@ -0,0 +1,176 @@
+// Claude Code's own login (its credentials and the `oauthAccount` in
+// ~/.claude.json), and which stored OAuth profile it belongs to. Claude Code
+// refreshes and rotates its token, and `/login` replaces it, without cswitch
+// knowing; this is how cswitch finds out.
+
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::claude_config;
+use crate::error::Result;
+use crate::keychain;
+use crate::profile::{Profile, ProfileStore, ProfileType};
+
+/// The login Claude Code is currently using.
+pub struct Login {
+    pub token_json: String,
+    pub account: Option<Value>,
+}
+
+impl Login {
+    /// Read the live login, or `None` when Claude Code has no OAuth
+    /// credentials.
+    pub fn read() -> Result<Option<Self>> {
+        let Ok(token_json) = keychain::get_claude_credentials() else {
+            return Ok(None);
+        };
+        if keychain::oauth_token_expiry(&token_json).is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Login {
+            token_json,
+            account: claude_config::read_oauth_account()?,
+        }))
+    }
+
+    /// Whether this login is `profile`'s: the same access or refresh token
+    /// as the one stored for it (the token has not rotated since), or the
+    /// same account as the one captured with its login.
+    pub fn belongs_to(&self, profile: &Profile) -> bool {
+        if profile.profile_type != ProfileType::OAuth {
+            return false;
+        }
+        if let Ok(stored) = keychain::get_oauth_token(&profile.name) {
+            if same_token(&self.token_json, &stored) {
+                return true;
+            }
+        }
+        match (&self.account, &profile.oauth_account) {
+            (Some(live), Some(captured)) => same_account(live, captured),
+            _ => false,
+        }
+    }
+
+    /// The stored OAuth profiles this login belongs to. Profiles in isolated
+    /// mode have their own login and are never matched.
+    pub fn owners<'a>(&self, store: &'a ProfileStore) -> Vec<&'a Profile> {
+        let mut owners: Vec<_> = store
+            .profiles
+            .values()
+            .filter(|p| !p.isolated && self.belongs_to(p))
+            .collect();
+        owners.sort_by(|a, b| a.name.cmp(&b.name));
+        owners
+    }
+
+    /// The email of the live account, for messages.
+    pub fn email(&self) -> Option<&str> {
+        self.account.as_ref()?.get("emailAddress")?.as_str()
+    }
+}
+
+fn oauth_field<'a>(token_json: &'a Value, key: &str) -> Option<&'a str> {
+    token_json
+        .get("claudeAiOauth")?
+        .get(key)?
+        .as_str()
+        .filter(|s| !s.is_empty())
+}
+
+fn same_token(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (serde_json::from_str::<Value>(a), serde_json::from_str::<Value>(b)) else {
+        return false;
+    };
+    ["accessToken", "refreshToken"].iter().any(|key| {
+        oauth_field(&a, key).is_some_and(|token| oauth_field(&b, key) == Some(token))
+    })
+}
+
+/// Same account UUID, or lacking one the same email, and never a different
+/// organization.
+fn same_account(a: &Value, b: &Value) -> bool {
+    let field = |v: &Value, key: &str| v.get(key).and_then(Value::as_str).map(str::to_string);
+    let same = |key: &str| match (field(a, key), field(b, key)) {
+        (Some(x), Some(y)) => Some(x == y),
+        _ => None,
+    };
+    if same("organizationUuid") == Some(false) {
+        return false;
+    }
+    same("accountUuid")
+        .or_else(|| same("emailAddress"))
+        .unwrap_or(false)
+}
+
+/// How Claude Code's login differs from what `profiles.json` says is active.
+pub enum Drift {
+    /// The login is these other profiles'
+    Other(Vec<String>),
+    /// The login matches no profile; its email, if known
+    Unknown(Option<String>),
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Other(names) => {
+                let names: Vec<String> = names.iter().map(|n| format!("'{n}'")).collect();
+                write!(f, "Claude Code is actually logged in as {}", names.join(" or "))
+            }
+            Drift::Unknown(Some(email)) => {
+                write!(f, "Claude Code is logged in as an unknown account ({email})")
+            }
+            Drift::Unknown(None) => write!(f, "Claude Code is logged in as an unknown account"),
+        }
+    }
+}
+
+/// Compare Claude Code's login with the active profile. Only an active OAuth
+/// profile (or none) can drift: API keys, providers and isolated profiles
+/// don't use the global login.
+pub fn drift(store: &ProfileStore, login: &Login) -> Option<Drift> {
+    let active = store.active.as_deref().and_then(|name| store.profiles.get(name));
+    if let Some(active) = active {
+        if active.isolated || active.profile_type != ProfileType::OAuth || login.belongs_to(active) {
+            return None;
+        }
+    }
+    let owners = login.owners(store);
+    if owners.is_empty() {
+        Some(Drift::Unknown(login.email().map(str::to_string)))
+    } else {
+        Some(Drift::Other(owners.iter().map(|p| p.name.clone()).collect()))
+    }
+}
+
+/// Save Claude Code's live credentials to the active profile before they are
+/// replaced, if they are that profile's and newer than its stored copy. This
+/// keeps a token Claude Code rotated while the profile was active. A login
+/// that is not the active profile's is left alone.
+pub fn sync_back(store: &mut ProfileStore) -> Result<()> {
+    let Some(active) = store.active.clone() else {
+        return Ok(());
+    };
+    let Some(profile) = store.profiles.get(&active).cloned() else {
+        return Ok(());
+    };
+    if profile.isolated || profile.profile_type != ProfileType::OAuth {
+        return Ok(());
+    }
+    let Some(login) = Login::read()? else {
+        return Ok(());
+    };
+    if !login.belongs_to(&profile) {
+        eprintln!(
+            "{} Claude Code is logged in as {}, not '{}'; its credentials were not saved to the profile. See 'cswitch reconcile'.",
+            "Warning:".yellow().bold(),
+            login.email().unwrap_or("another account"),
+            active
+        );
+        return Ok(());
+    }
+    if keychain::store_oauth_token_if_newer(&active, &login.token_json)? {
+        store.refresh_account(&active, &login.token_json)?;
+    }
+    Ok(())
+}
@@ -1,10 +1,22 @@
+mod assets;
 mod cli;
 mod claude_config;
 mod commands;
+mod config;
 mod error;
+mod fsutil;
+mod isolated;
 mod keychain;
+mod live;
 mod oauth;
+mod pin;
 mod profile;
+mod profile_env;
+mod pty;
+mod schema;
+mod secrets;
+mod settings_overlay;
+mod token_endpoint;
 
 use clap::Parser;
 use cli::{Cli, Commands};
@@ -14,14 +26,28 @@ fn main() {
     let cli = Cli::parse();
 
     let result = match cli.command {
-        Commands::Add { name } => commands::add::run(name),
+        Commands::Add { name, setup_token } => commands::add::run(name, setup_token),
         Commands::Use { name } => commands::use_profile::run(name),
+        Commands::Env { profile, shell, unset } => commands::env::run(profile, shell, unset),
+        Commands::Exec { profile, command } => commands::exec::run(profile, command),
+        Commands::SetEnv { name, vars } => commands::profile_env::set(name, vars),
+        Commands::UnsetEnv { name, keys } => commands::profile_env::unset(name, keys),
         Commands::List => commands::list::run(),
         Commands::Current => commands::current::run(),
+        Commands::Resolve => commands::resolve::run(),
+        Commands::Pin { name } => commands::pin::run(name),
         Commands::Remove { name } => commands::remove::run(name),
         Commands::Import { name } => commands::import::run(name),
+        Commands::Reconcile => commands::reconcile::run(),
         Commands::Init => commands::init::run(),
+        Commands::Hook { shell } => commands::hook::run(shell),
+        Commands::HookSwitch { shell, no_switch } => commands::hook::switch(shell, no_switch),
+        Commands::Assets { command } => commands::assets::run(command),
+        Commands::Settings { command } => commands::settings::run(command),
+        Commands::Isolate { name, off } => commands::isolate::run(name, off),
+        Commands::Doctor { fix } => commands::doctor::run(fix),
         Commands::Update => commands::update::run(),
+        Commands::Secrets { command } => commands::secrets::run(command),
         Commands::EmitKey => commands::emit_key::run(),
     };
 
@@ -1,12 +1,167 @@
-// Login to Claude by delegating to `claude auth login`.
+// Login to Claude: cswitch's own browser flow (authorization code + PKCE
+// with a loopback redirect), or delegating to `claude auth login` /
+// `claude setup-token` on a pseudo-terminal.
 
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
 use colored::Colorize;
-use std::process::Command;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use url::Url;
 
 use crate::error::{CswitchError, Result};
 use crate::keychain;
+use crate::pty;
+use crate::token_endpoint::{self, Endpoint};
 
-/// Run `claude auth login` then capture the credentials from the Keychain.
+/// The scopes Claude Code asks for.
+const SCOPES: &str = "org:create_api_key user:profile user:inference";
+
+/// How long to wait for the browser to come back.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Log in through the browser and store the tokens in the profile, in the
+/// `claudeAiOauth` shape Claude Code uses. Claude Code's own login is not
+/// touched. Returns the account, shaped like `oauthAccount` in ~/.claude.json.
+pub fn run_native_oauth_flow(profile_name: &str) -> Result<Option<Value>> {
+    let endpoint = Endpoint::load()?;
+
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .map_err(|e| CswitchError::OAuth(format!("Cannot listen for the login redirect: {e}")))?;
+    let redirect_uri = format!("http://localhost:{}/callback", listener.local_addr()?.port());
+
+    let verifier = random_token();
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_token();
+
+    let authorize_url = Url::parse_with_params(
+        &endpoint.authorize_url,
+        [
+            ("client_id", endpoint.client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", &redirect_uri),
+            ("scope", SCOPES),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+            ("state", &state),
+        ],
+    )
+    .map_err(|e| CswitchError::OAuth(format!("Invalid authorize URL {}: {e}", endpoint.authorize_url)))?;
+
+    println!("{} Opening your browser to log in...", "→".blue().bold());
+    println!("  If it doesn't open, visit:\n  {authorize_url}");
+    open_browser(authorize_url.as_str());
+
+    let code = wait_for_code(&listener, &state)?;
+    let response = token_endpoint::exchange_code(&endpoint, &code, &verifier, &redirect_uri, &state)?;
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    keychain::set_oauth_token(profile_name, &response.credentials_json(now_ms))?;
+
+    println!("{} Authentication successful.", "✓".green().bold());
+    Ok(response.oauth_account())
+}
+
+/// 32 random bytes, base64url: a PKCE verifier (RFC 7636) or a `state`.
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn open_browser(url: &str) {
+    let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+    let _ = Command::new(opener)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Serve the loopback redirect until it brings back a code for `state`.
+fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String> {
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + LOGIN_TIMEOUT;
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(CswitchError::OAuth("Timed out waiting for the browser login".into()));
+                }
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(result) = handle_redirect(stream, state)? {
+            return result;
+        }
+    }
+}
+
+/// Answer one request to the listener. `None` when it wasn't the redirect
+/// (a favicon, say), so waiting goes on.
+fn handle_redirect(mut stream: TcpStream, state: &str) -> Result<Option<Result<String>>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request_line = String::new();
+    if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+        return Ok(None);
+    }
+    // `GET /callback?code=...&state=... HTTP/1.1`
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let Ok(url) = Url::parse(&format!("http://localhost{target}")) else {
+        respond(&mut stream, "400 Bad Request", "Bad request.");
+        return Ok(None);
+    };
+    if url.path() != "/callback" {
+        respond(&mut stream, "404 Not Found", "Not found.");
+        return Ok(None);
+    }
+
+    let param = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+    let result = if let Some(error) = param("error") {
+        let detail = param("error_description").map(|d| format!(": {d}")).unwrap_or_default();
+        Err(CswitchError::OAuth(format!("Login was not authorized ({error}{detail})")))
+    } else if param("state").as_deref() != Some(state) {
+        Err(CswitchError::OAuth("Login redirect had the wrong state; try again".into()))
+    } else if let Some(code) = param("code") {
+        Ok(code)
+    } else {
+        Err(CswitchError::OAuth("Login redirect had no code".into()))
+    };
+
+    match &result {
+        Ok(_) => respond(&mut stream, "200 OK", "Logged in. You can close this tab and return to the terminal."),
+        Err(e) => respond(&mut stream, "400 Bad Request", &format!("Login failed: {e}")),
+    }
+    Ok(Some(result))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = format!("<!doctype html><title>cswitch</title><p>{}</p>\n", escape_html(message));
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Run `claude auth login` then capture the credentials Claude Code stored.
+/// This replaces Claude Code's current login.
 pub fn run_oauth_flow(profile_name: &str) -> Result<()> {
     // Check that `claude` is installed
     let has_claude = Command::new("claude")
@@ -25,19 +180,19 @@ pub fn run_oauth_flow(profile_name: &str) -> Result<()> {
         "→".blue().bold()
     );
 
-    let status = Command::new("script")
-        .args(["-q", "/dev/null", "claude", "auth", "login"])
-        .status()
+    let mut login = Command::new("claude");
+    login.args(["auth", "login"]);
+    let outcome = pty::run(login)
         .map_err(|e| CswitchError::OAuth(format!("Failed to run 'claude auth login': {e}")))?;
 
-    if !status.success() {
-        return Err(CswitchError::OAuth("'claude auth login' failed".into()));
+    if !outcome.status.success() {
+        return Err(CswitchError::OAuth(format!("'claude auth login' failed ({})", outcome.status)));
     }
 
-    // Grab the fresh credentials from the Keychain
+    // Grab the fresh credentials from the Keychain / credentials file
     let creds = keychain::get_claude_credentials().map_err(|_| {
         CswitchError::OAuth(
-            "Login succeeded but credentials not found in Keychain".into(),
+            "Login succeeded but Claude Code credentials not found".into(),
         )
     })?;
 
@@ -46,3 +201,55 @@ pub fn run_oauth_flow(profile_name: &str) -> Result<()> {
     println!("{} Authentication successful.", "✓".green().bold());
     Ok(())
 }
+
+/// Run `claude setup-token` and pick the long-lived token out of its output.
+/// `claude` needs a terminal, so it gets a pseudo-terminal whose output is
+/// both shown and kept in memory.
+pub fn run_setup_token_flow() -> Result<String> {
+    println!("{} Running 'claude setup-token'...", "→".blue().bold());
+
+    let mut setup = Command::new("claude");
+    setup.arg("setup-token");
+    let outcome = pty::run(setup)
+        .map_err(|e| CswitchError::OAuth(format!("Failed to run 'claude setup-token': {e}")))?;
+
+    if !outcome.status.success() {
+        return Err(CswitchError::OAuth(format!("'claude setup-token' failed ({})", outcome.status)));
+    }
+
+    find_setup_token(&String::from_utf8_lossy(&outcome.output))
+        .ok_or_else(|| CswitchError::OAuth("No token found in the output of 'claude setup-token'".into()))
+}
+
+/// The last `sk-ant-oat…` token in terminal output, ignoring escape sequences.
+fn find_setup_token(output: &str) -> Option<String> {
+    let text = strip_ansi(output);
+    let start = text.rfind("sk-ant-oat")?;
+    let token: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    Some(token)
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        // CSI sequences end with a letter; skip the lone character otherwise
+        if chars.next_if_eq(&'[').is_some() {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    out
+}
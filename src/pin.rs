@@ -0,0 +1,136 @@
+// Per-directory profile pinning: a `.cswitch` file (just a profile name) or a
+// `.cswitch.toml` (`profile = "name"`), found by walking up from the current
+// directory like `.nvmrc`. A pin takes precedence over `directory_rules` in
+// config.json, which take precedence over the global `active`.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, DirectoryRule};
+use crate::error::{CswitchError, Result};
+use crate::profile::{Profile, ProfileStore};
+
+pub const PIN_FILE: &str = ".cswitch";
+pub const PIN_TOML_FILE: &str = ".cswitch.toml";
+
+#[derive(Deserialize)]
+struct PinToml {
+    profile: String,
+}
+
+/// A pin file and the profile it names.
+pub struct Pin {
+    pub profile: String,
+    pub path: PathBuf,
+}
+
+/// Where a resolved profile came from.
+pub enum Source {
+    Pin(PathBuf),
+    Rule(PathBuf),
+    Active,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Pin(path) => write!(f, "pinned by {}", path.display()),
+            Source::Rule(path) => write!(f, "rule for {}", path.display()),
+            Source::Active => write!(f, "global active profile"),
+        }
+    }
+}
+
+pub struct Resolved<'a> {
+    pub profile: &'a Profile,
+    pub source: Source,
+}
+
+fn read_pin(path: &Path) -> Result<String> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| CswitchError::Config(format!("Cannot read {}: {e}", path.display())))?;
+
+    let profile = if path.file_name().is_some_and(|n| n == PIN_TOML_FILE) {
+        toml::from_str::<PinToml>(&data)
+            .map_err(|e| CswitchError::Config(format!("Invalid {}: {e}", path.display())))?
+            .profile
+    } else {
+        // First line that isn't blank or a `#` comment
+        data.lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let profile = profile.trim().to_string();
+    if profile.is_empty() {
+        return Err(CswitchError::Config(format!("{} doesn't name a profile", path.display())));
+    }
+    Ok(profile)
+}
+
+/// Find the nearest pin in `start` or one of its ancestors.
+pub fn find(start: &Path) -> Result<Option<Pin>> {
+    for dir in start.ancestors() {
+        for file in [PIN_FILE, PIN_TOML_FILE] {
+            let path = dir.join(file);
+            if path.is_file() {
+                let profile = read_pin(&path)?;
+                return Ok(Some(Pin { profile, path }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Find the most specific directory rule covering `dir`.
+pub fn find_rule(dir: &Path, rules: &[DirectoryRule]) -> Option<(PathBuf, String)> {
+    rules
+        .iter()
+        .filter_map(|rule| Some((rule.expanded_path()?, rule.profile.clone())))
+        .filter(|(path, _)| dir.starts_with(path))
+        .max_by_key(|(path, _)| path.components().count())
+}
+
+fn lookup<'a>(store: &'a ProfileStore, name: &str, source: &Source) -> Result<&'a Profile> {
+    store.profiles.get(name).ok_or_else(|| CswitchError::PinnedProfileNotFound {
+        name: name.to_string(),
+        origin: source.to_string(),
+    })
+}
+
+/// Resolve the profile a pin or directory rule selects for the current
+/// directory, if any.
+pub fn resolve_local(store: &ProfileStore) -> Result<Option<Resolved<'_>>> {
+    let cwd = std::env::current_dir()?;
+    let (name, source) = if let Some(pin) = find(&cwd)? {
+        (pin.profile, Source::Pin(pin.path))
+    } else if let Some((path, name)) = find_rule(&cwd, &Config::load()?.directory_rules) {
+        (name, Source::Rule(path))
+    } else {
+        return Ok(None);
+    };
+    let profile = lookup(store, &name, &source)?;
+    Ok(Some(Resolved { profile, source }))
+}
+
+/// Resolve the profile for the current directory: the nearest pin, else a
+/// directory rule, else the global active profile.
+pub fn resolve(store: &ProfileStore) -> Result<Resolved<'_>> {
+    if let Some(resolved) = resolve_local(store)? {
+        return Ok(resolved);
+    }
+    Ok(Resolved {
+        profile: store.get_active()?,
+        source: Source::Active,
+    })
+}
+
+/// Write a `.cswitch` pin for `profile` into `dir`.
+pub fn write(dir: &Path, profile: &str) -> Result<PathBuf> {
+    let path = dir.join(PIN_FILE);
+    fs::write(&path, format!("{profile}\n"))?;
+    Ok(path)
+}
@@ -1,16 +1,106 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::claude_config::EnvEntry;
 use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::schema::{self, Migration};
+use crate::settings_overlay::OverlayEntry;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ProfileType {
     ApiKey,
     OAuth,
+    /// Claude through Amazon Bedrock, authenticated by the AWS SDK chain.
+    Bedrock {
+        aws_profile: Option<String>,
+        region: String,
+        /// Model ID or inference profile ARN for `ANTHROPIC_MODEL`
+        model: Option<String>,
+        /// Model ID or ARN for `ANTHROPIC_SMALL_FAST_MODEL`
+        small_fast_model: Option<String>,
+    },
+    /// Claude through Google Vertex AI, authenticated by gcloud credentials.
+    Vertex { project_id: String, region: String },
+    /// An LLM gateway that takes a bearer token (`ANTHROPIC_AUTH_TOKEN`).
+    Gateway { base_url: String },
+    /// A long-lived token from `claude setup-token` (`CLAUDE_CODE_OAUTH_TOKEN`).
+    SetupToken { expires_at: Option<DateTime<Utc>> },
+}
+
+/// How long `claude setup-token` tokens are valid.
+pub const SETUP_TOKEN_LIFETIME_DAYS: i64 = 365;
+
+/// Warn about a setup token this close to expiry.
+const SETUP_TOKEN_WARNING_DAYS: i64 = 30;
+
+impl ProfileType {
+    /// Variables Claude Code needs in settings.json's `env` for this type.
+    pub fn provider_env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        match self {
+            ProfileType::ApiKey | ProfileType::OAuth | ProfileType::SetupToken { .. } => {}
+            ProfileType::Bedrock {
+                aws_profile,
+                region,
+                model,
+                small_fast_model,
+            } => {
+                env.insert("CLAUDE_CODE_USE_BEDROCK".into(), "1".into());
+                env.insert("AWS_REGION".into(), region.clone());
+                if let Some(aws_profile) = aws_profile {
+                    env.insert("AWS_PROFILE".into(), aws_profile.clone());
+                }
+                if let Some(model) = model {
+                    env.insert("ANTHROPIC_MODEL".into(), model.clone());
+                }
+                if let Some(small_fast_model) = small_fast_model {
+                    env.insert("ANTHROPIC_SMALL_FAST_MODEL".into(), small_fast_model.clone());
+                }
+            }
+            ProfileType::Vertex { project_id, region } => {
+                env.insert("CLAUDE_CODE_USE_VERTEX".into(), "1".into());
+                env.insert("ANTHROPIC_VERTEX_PROJECT_ID".into(), project_id.clone());
+                env.insert("CLOUD_ML_REGION".into(), region.clone());
+            }
+            ProfileType::Gateway { base_url } => {
+                env.insert("ANTHROPIC_BASE_URL".into(), base_url.clone());
+            }
+        }
+        env
+    }
+
+    /// Whether Claude Code gets this type's credential from `apiKeyHelper`
+    /// (`cswitch emit-key`), which it sends both as `x-api-key` and as
+    /// `Authorization: Bearer`. Keeps the key out of settings.json.
+    pub fn uses_key_helper(&self) -> bool {
+        matches!(self, ProfileType::ApiKey | ProfileType::Gateway { .. })
+    }
+
+    /// Short description of where a cloud provider or gateway profile points,
+    /// or when a setup token expires.
+    pub fn details(&self) -> Option<String> {
+        match self {
+            ProfileType::ApiKey | ProfileType::OAuth => None,
+            ProfileType::Bedrock {
+                aws_profile, region, ..
+            } => Some(match aws_profile {
+                Some(p) => format!("{region}, AWS profile {p}"),
+                None => region.clone(),
+            }),
+            ProfileType::Vertex { project_id, region } => Some(format!("{project_id}, {region}")),
+            ProfileType::Gateway { base_url } => Some(base_url.clone()),
+            ProfileType::SetupToken { expires_at } => Some(match expires_at {
+                Some(at) if *at <= Utc::now() => format!("expired {}", at.format("%Y-%m-%d")),
+                Some(at) => format!("expires {}", at.format("%Y-%m-%d")),
+                None => "no expiry recorded".to_string(),
+            }),
+        }
+    }
 }
 
 impl std::fmt::Display for ProfileType {
@@ -18,10 +108,69 @@ impl std::fmt::Display for ProfileType {
         match self {
             ProfileType::ApiKey => write!(f, "api-key"),
             ProfileType::OAuth => write!(f, "oauth"),
+            ProfileType::Bedrock { .. } => write!(f, "bedrock"),
+            ProfileType::Vertex { .. } => write!(f, "vertex"),
+            ProfileType::Gateway { .. } => write!(f, "gateway"),
+            ProfileType::SetupToken { .. } => write!(f, "setup-token"),
         }
     }
 }
 
+/// Who an OAuth profile is logged in as and on which plan: the
+/// `claudeAiOauth` fields of its token, plus the email and organization from
+/// the `oauthAccount` captured with the login.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// `pro`, `max`, `team`, `enterprise`...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_tier: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+impl Account {
+    /// Read from a stored token JSON and an `oauthAccount` section. `None`
+    /// when neither says anything.
+    pub fn from_login(token_json: &str, oauth_account: Option<&serde_json::Value>) -> Option<Self> {
+        let creds: serde_json::Value = serde_json::from_str(token_json).unwrap_or_default();
+        let oauth = creds.get("claudeAiOauth");
+        let text = |value: Option<&serde_json::Value>, key: &str| {
+            value
+                .and_then(|v| v.get(key))
+                .and_then(serde_json::Value::as_str)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let account = Account {
+            email: text(oauth_account, "emailAddress"),
+            organization: text(oauth_account, "organizationName"),
+            subscription_type: text(oauth, "subscriptionType"),
+            rate_limit_tier: text(oauth, "rateLimitTier"),
+            scopes: oauth
+                .and_then(|o| o.get("scopes"))
+                .and_then(serde_json::Value::as_array)
+                .map(|scopes| scopes.iter().filter_map(|s| s.as_str().map(str::to_string)).collect())
+                .unwrap_or_default(),
+        };
+        (account != Account::default()).then_some(account)
+    }
+
+    /// `email, plan`, whichever are known.
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.email, &self.subscription_type]
+            .into_iter()
+            .filter_map(|s| s.as_deref())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
     pub name: String,
@@ -29,12 +178,143 @@ pub struct Profile {
     pub label: Option<String>,
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
+    /// Extra variables merged into the `env` block of Claude Code's
+    /// settings.json while this profile is active.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Settings deep-merged into Claude Code's settings.json while this
+    /// profile is active (see `settings_overlay`).
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+    /// Use a config directory of its own (see `isolated`) instead of
+    /// Claude Code's global one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub isolated: bool,
+    /// Variables cswitch has written to the `env` of the isolated
+    /// settings.json, and what they replaced.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub isolated_env: Vec<EnvEntry>,
+    /// The overlay as written to the isolated settings.json.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub isolated_settings: Vec<OverlayEntry>,
+    /// The `oauthAccount` section of ~/.claude.json captured with an OAuth
+    /// login, restored there on `use`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth_account: Option<serde_json::Value>,
+    /// Identity and plan of an OAuth login, kept current on token refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<Account>,
+}
+
+/// Check that `name` can be a profile name. Names become directory names
+/// under cswitch's data dir, so only a single plain path component is
+/// allowed: no separators, `.`, `..` or absolute paths.
+pub fn validate_name(name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(c)), None) if c == name => Ok(()),
+        _ => Err(CswitchError::InvalidProfileName(name.to_string())),
+    }
+}
+
+impl Profile {
+    pub fn new(name: String, profile_type: ProfileType, label: Option<String>) -> Self {
+        Profile {
+            name,
+            profile_type,
+            label,
+            created_at: Utc::now(),
+            last_used: None,
+            env: BTreeMap::new(),
+            settings: serde_json::Map::new(),
+            isolated: false,
+            isolated_env: Vec::new(),
+            isolated_settings: Vec::new(),
+            oauth_account: None,
+            account: None,
+        }
+    }
+
+    /// What the type says about the target (region, URL, expiry), or for
+    /// an OAuth profile the account and plan.
+    pub fn details(&self) -> Option<String> {
+        match self.profile_type {
+            ProfileType::OAuth => self.account.as_ref().and_then(Account::summary),
+            _ => self.profile_type.details(),
+        }
+    }
+
+    /// The label and details, as shown next to the name in lists.
+    pub fn description(&self) -> String {
+        match (self.label.as_deref(), self.details()) {
+            (Some(label), Some(details)) => format!("{label} ({details})"),
+            (Some(label), None) => label.to_string(),
+            (None, Some(details)) => details,
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Everything this profile puts in settings.json's `env`: the provider
+    /// variables, then the user's own block, which wins on conflicts.
+    pub fn settings_env(&self) -> BTreeMap<String, String> {
+        let mut env = self.profile_type.provider_env();
+        env.extend(self.env.clone());
+        env
+    }
+
+    /// A warning when this is a setup-token profile whose token has expired
+    /// or expires within 30 days.
+    pub fn expiry_warning(&self) -> Option<String> {
+        let ProfileType::SetupToken { expires_at: Some(at) } = self.profile_type else {
+            return None;
+        };
+        let now = Utc::now();
+        if at <= now {
+            Some(format!(
+                "The token of '{}' expired on {}. Run 'cswitch add {}' with a new one.",
+                self.name,
+                at.format("%Y-%m-%d"),
+                self.name
+            ))
+        } else if at - now < Duration::days(SETUP_TOKEN_WARNING_DAYS) {
+            Some(format!(
+                "The token of '{}' expires on {}. Run 'claude setup-token' and 'cswitch add {}' to replace it.",
+                self.name,
+                at.format("%Y-%m-%d"),
+                self.name
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Upgrades for profiles.json, oldest first (see `schema`).
+const MIGRATIONS: &[Migration] = &[schema::unversioned_to_v1];
+
+fn write_value(path: &Path, value: &serde_json::Value) -> Result<()> {
+    let data = serde_json::to_string_pretty(value)?;
+    fsutil::write_atomic(path, data.as_bytes(), 0o644)?;
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProfileStore {
     pub active: Option<String>,
     pub profiles: HashMap<String, Profile>,
+    /// Variables cswitch put in settings.json's `env` for the active profile,
+    /// and the user's values they replaced, so the next switch can take them
+    /// out again and put those back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub managed_env: Vec<EnvEntry>,
+    /// The active profile's settings overlay as written to settings.json,
+    /// undone on the next switch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub applied_settings: Vec<OverlayEntry>,
+    /// Assets symlinked into Claude Code's config dir for the active profile
+    /// (see `assets`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linked_assets: Vec<String>,
 }
 
 impl ProfileStore {
@@ -47,53 +327,86 @@ impl ProfileStore {
 
     pub fn load() -> Result<Self> {
         let path = Self::config_path()?;
-        if !path.exists() {
-            return Ok(Self::default());
+        let value = schema::load(
+            &path,
+            MIGRATIONS,
+            || Ok(serde_json::from_str(&fs::read_to_string(&path)?)?),
+            |value| write_value(&path, value),
+        )?;
+        match value {
+            Some(value) => Ok(serde_json::from_value(value)?),
+            None => Ok(Self::default()),
         }
-        let data = fs::read_to_string(&path)?;
-        let store: Self = serde_json::from_str(&data)?;
-        Ok(store)
     }
 
+    /// Write the store as-is. Prefer [`ProfileStore::update`] for changes, which
+    /// can't lose a concurrent writer's update.
     pub fn save(&self) -> Result<()> {
-        let path = Self::config_path()?;
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let data = serde_json::to_string_pretty(self)?;
-        fs::write(&path, data)?;
-        Ok(())
+        let mut value = serde_json::to_value(self)?;
+        schema::stamp(&mut value, MIGRATIONS);
+        write_value(&Self::config_path()?, &value)
+    }
+
+    /// Run a read-modify-write cycle under the store's lock: reload from disk,
+    /// apply `f`, save. `self` holds the saved state afterwards.
+    pub fn update<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let _lock = fsutil::lock(&Self::config_path()?)?;
+        *self = Self::load()?;
+        let out = f(self)?;
+        self.save()?;
+        Ok(out)
     }
 
     pub fn add_profile(&mut self, profile: Profile) -> Result<()> {
-        if self.profiles.contains_key(&profile.name) {
-            return Err(CswitchError::ProfileAlreadyExists(profile.name.clone()));
-        }
-        self.profiles.insert(profile.name.clone(), profile);
-        self.save()
+        self.update(|store| {
+            if store.profiles.contains_key(&profile.name) {
+                return Err(CswitchError::ProfileAlreadyExists(profile.name.clone()));
+            }
+            store.profiles.insert(profile.name.clone(), profile);
+            Ok(())
+        })
     }
 
     pub fn remove_profile(&mut self, name: &str) -> Result<Profile> {
-        let profile = self
-            .profiles
-            .remove(name)
-            .ok_or_else(|| CswitchError::ProfileNotFound(name.into()))?;
-        if self.active.as_deref() == Some(name) {
-            self.active = None;
-        }
-        self.save()?;
-        Ok(profile)
+        self.update(|store| {
+            let profile = store
+                .profiles
+                .remove(name)
+                .ok_or_else(|| CswitchError::ProfileNotFound(name.into()))?;
+            if store.active.as_deref() == Some(name) {
+                store.active = None;
+            }
+            Ok(profile)
+        })
     }
 
-    pub fn set_active(&mut self, name: &str) -> Result<()> {
-        if !self.profiles.contains_key(name) {
-            return Err(CswitchError::ProfileNotFound(name.into()));
+    /// Re-read an OAuth profile's account from its current token and save it
+    /// if it changed. Called wherever the token may have been refreshed.
+    pub fn refresh_account(&mut self, name: &str, token_json: &str) -> Result<()> {
+        let Some(profile) = self.profiles.get(name) else {
+            return Ok(());
+        };
+        let account = Account::from_login(token_json, profile.oauth_account.as_ref());
+        if account.is_none() || account == profile.account {
+            return Ok(());
         }
+        self.update(|store| {
+            if let Some(profile) = store.profiles.get_mut(name) {
+                profile.account = account;
+            }
+            Ok(())
+        })
+    }
+
+    /// Make `name` active in memory, without saving.
+    pub fn mark_active(&mut self, name: &str) -> Result<()> {
+        let profile = self
+            .profiles
+            .get_mut(name)
+            .ok_or_else(|| CswitchError::ProfileNotFound(name.into()))?;
+        profile.last_used = Some(Utc::now());
         self.active = Some(name.to_string());
-        if let Some(profile) = self.profiles.get_mut(name) {
-            profile.last_used = Some(Utc::now());
-        }
-        self.save()
+        Ok(())
     }
 
     pub fn get_active(&self) -> Result<&Profile> {
@@ -112,3 +425,131 @@ impl ProfileStore {
             .ok_or_else(|| CswitchError::ProfileNotFound(name.into()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A profiles.json in a directory of its own under the temp dir.
+    fn temp_store(name: &str, value: &serde_json::Value) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cswitch-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("profiles.json");
+        fs::write(&path, serde_json::to_string_pretty(value).unwrap()).unwrap();
+        path
+    }
+
+    fn load(path: &Path) -> Result<Option<serde_json::Value>> {
+        schema::load(
+            path,
+            MIGRATIONS,
+            || Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+            |value| write_value(path, value),
+        )
+    }
+
+    /// profiles.json as written before versioning.
+    fn unversioned() -> serde_json::Value {
+        json!({
+            "active": "work",
+            "profiles": {
+                "work": {
+                    "name": "work",
+                    "profile_type": "api-key",
+                    "label": "Acme",
+                    "created_at": "2025-01-01T00:00:00Z",
+                    "last_used": "2025-02-01T00:00:00Z",
+                },
+                "personal": {
+                    "name": "personal",
+                    "profile_type": "o-auth",
+                    "label": null,
+                    "created_at": "2025-01-01T00:00:00Z",
+                    "last_used": null,
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn upgrades_an_unversioned_file() {
+        let path = temp_store("unversioned", &unversioned());
+        let original = fs::read_to_string(&path).unwrap();
+
+        let value = load(&path).unwrap().unwrap();
+        assert_eq!(value["version"], json!(MIGRATIONS.len()));
+        assert_eq!(value["written_by"], json!(env!("CARGO_PKG_VERSION")));
+        let backup = path.with_file_name("profiles.json.v0.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), original);
+        let on_disk: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk, value);
+
+        let store: ProfileStore = serde_json::from_value(value).unwrap();
+        assert_eq!(store.active.as_deref(), Some("work"));
+        let work = store.get_profile("work").unwrap();
+        assert_eq!(work.profile_type, ProfileType::ApiKey);
+        assert!(work.env.is_empty() && work.settings.is_empty() && !work.isolated);
+        assert_eq!(store.get_profile("personal").unwrap().profile_type, ProfileType::OAuth);
+        assert!(store.managed_env.is_empty() && store.applied_settings.is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn leaves_a_current_file_alone() {
+        let mut value = unversioned();
+        schema::stamp(&mut value, MIGRATIONS);
+        let path = temp_store("current", &value);
+        let original = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(load(&path).unwrap(), Some(value));
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!path.with_file_name("profiles.json.v1.bak").exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_newer_file() {
+        let mut value = unversioned();
+        value["version"] = json!(MIGRATIONS.len() + 1);
+        value["written_by"] = json!("99.0.0");
+        let path = temp_store("newer", &value);
+
+        match load(&path) {
+            Err(CswitchError::NewerSchema { file, version, written_by }) => {
+                assert_eq!(file, "profiles.json");
+                assert_eq!(version, MIGRATIONS.len() as u64 + 1);
+                assert_eq!(written_by, "99.0.0");
+            }
+            other => panic!("expected a newer schema error, got {other:?}"),
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn round_trips_every_field() {
+        let mut profile = Profile::new(
+            "gw".into(),
+            ProfileType::Gateway { base_url: "https://llm.example.com".into() },
+            None,
+        );
+        profile.env.insert("HTTPS_PROXY".into(), "http://proxy:3128".into());
+        profile.settings.insert("model".into(), json!("opus"));
+        profile.isolated = true;
+        profile.isolated_env.push(EnvEntry { key: "HTTPS_PROXY".into(), previous: Some(json!("x")) });
+        let mut store = ProfileStore {
+            active: Some("gw".into()),
+            ..Default::default()
+        };
+        store.profiles.insert("gw".into(), profile);
+        store.linked_assets.push("CLAUDE.md".into());
+
+        let mut value = serde_json::to_value(&store).unwrap();
+        schema::stamp(&mut value, MIGRATIONS);
+        let loaded: ProfileStore = serde_json::from_value(value.clone()).unwrap();
+        let mut again = serde_json::to_value(&loaded).unwrap();
+        schema::stamp(&mut again, MIGRATIONS);
+        assert_eq!(again, value);
+    }
+}
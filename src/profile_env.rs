@@ -0,0 +1,74 @@
+// Environment a process needs to run as a profile, shared by `exec` and `env`.
+
+use colored::Colorize;
+
+use crate::error::Result;
+use crate::isolated;
+use crate::keychain;
+use crate::profile::{Profile, ProfileType};
+
+/// One variable to set (`Some`) or clear (`None`).
+pub type EnvVar = (String, Option<String>);
+
+fn set(name: &str, value: impl Into<String>) -> EnvVar {
+    (name.to_string(), Some(value.into()))
+}
+
+fn unset(name: &str) -> EnvVar {
+    (name.to_string(), None)
+}
+
+/// Whether `profile` runs with `CLAUDE_CONFIG_DIR` set to its own directory.
+fn uses_config_dir(profile: &Profile) -> bool {
+    profile.isolated || profile.profile_type == ProfileType::OAuth
+}
+
+/// Variables that select `profile`'s account, fetching its credentials.
+pub fn for_profile(profile: &Profile) -> Result<Vec<EnvVar>> {
+    let mut vars = Vec::new();
+    if uses_config_dir(profile) {
+        let dir = isolated::prepare(profile)?;
+        vars.push(set("CLAUDE_CONFIG_DIR", dir.to_string_lossy()));
+    } else if isolated::current_profile().is_some() {
+        // Don't inherit another profile's directory
+        vars.push(unset("CLAUDE_CONFIG_DIR"));
+    }
+
+    if profile.profile_type == ProfileType::ApiKey {
+        vars.push(set("ANTHROPIC_API_KEY", keychain::get_api_key(&profile.name)?));
+    } else {
+        // An exported key would take precedence over the profile's own login
+        vars.push(unset("ANTHROPIC_API_KEY"));
+    }
+    match profile.profile_type {
+        ProfileType::Gateway { .. } => {
+            vars.push(set("ANTHROPIC_AUTH_TOKEN", keychain::get_auth_token(&profile.name)?));
+        }
+        ProfileType::SetupToken { .. } => {
+            vars.push(set("CLAUDE_CODE_OAUTH_TOKEN", keychain::get_setup_token(&profile.name)?));
+        }
+        _ => {}
+    }
+    if let Some(warning) = profile.expiry_warning() {
+        eprintln!("{} {warning}", "Warning:".yellow().bold());
+    }
+    vars.extend(profile.settings_env().into_iter().map(|(k, v)| (k, Some(v))));
+    Ok(vars)
+}
+
+/// Names of every variable [`for_profile`] may set or clear for `profile`,
+/// without fetching any credentials.
+pub fn names(profile: &Profile) -> Vec<String> {
+    let mut names = Vec::new();
+    if uses_config_dir(profile) {
+        names.push("CLAUDE_CONFIG_DIR".to_string());
+    }
+    names.push("ANTHROPIC_API_KEY".to_string());
+    match profile.profile_type {
+        ProfileType::Gateway { .. } => names.push("ANTHROPIC_AUTH_TOKEN".to_string()),
+        ProfileType::SetupToken { .. } => names.push("CLAUDE_CODE_OAUTH_TOKEN".to_string()),
+        _ => {}
+    }
+    names.extend(profile.settings_env().into_keys());
+    names
+}
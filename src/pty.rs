@@ -0,0 +1,231 @@
+// Run an interactive program on a pseudo-terminal of its own. `claude auth
+// login` and `claude setup-token` insist on a terminal; giving them one from
+// here works the same on Linux and macOS, unlike the two `script` dialects,
+// and lets cswitch see what they print.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How often the relay checks whether the program has exited, in ms.
+const EXIT_POLL_MS: libc::c_int = 100;
+
+/// What the program did: its exit status and everything it printed.
+pub struct Outcome {
+    pub status: ExitStatus,
+    pub output: Vec<u8>,
+}
+
+/// Run `command` with a new pseudo-terminal as its controlling terminal and
+/// stdin/stdout/stderr. Our stdin is passed through to it (in raw mode when
+/// it is a terminal, so keys reach the program as typed, and resizes follow)
+/// and its output is shown as it comes and also returned. This returns once
+/// the program exits, even if something it started (a browser) still holds
+/// the terminal.
+pub fn run(mut command: Command) -> io::Result<Outcome> {
+    let stdin_fd = io::stdin().as_raw_fd();
+    let saved = termios(stdin_fd);
+    let (master, slave) = open_pty(saved.as_ref(), window_size(stdin_fd).as_ref())?;
+
+    let stdio = || slave.try_clone().map(Stdio::from);
+    command.stdin(stdio()?).stdout(stdio()?).stderr(stdio()?);
+    // SAFETY: setsid and ioctl are async-signal-safe, as pre_exec requires
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+    // Only the child may hold the terminal, so reads see its end: close our
+    // copies, including those `command` keeps for its stdio
+    drop(command);
+    drop(slave);
+
+    let _raw = saved.map(|saved| RawMode::enter(stdin_fd, saved));
+    let _winch = saved.is_some().then(WinchHandler::install);
+    relay(stdin_fd, master, &mut child)
+}
+
+fn termios(fd: RawFd) -> Option<libc::termios> {
+    let mut t = std::mem::MaybeUninit::<libc::termios>::uninit();
+    // SAFETY: tcgetattr fills `t` when it succeeds
+    (unsafe { libc::tcgetattr(fd, t.as_mut_ptr()) } == 0).then(|| unsafe { t.assume_init() })
+}
+
+fn window_size(fd: RawFd) -> Option<libc::winsize> {
+    let mut ws = std::mem::MaybeUninit::<libc::winsize>::uninit();
+    // SAFETY: TIOCGWINSZ fills `ws` when it succeeds
+    (unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, ws.as_mut_ptr()) } == 0).then(|| unsafe { ws.assume_init() })
+}
+
+/// A new master/slave pair, set up like our terminal when there is one.
+fn open_pty(term: Option<&libc::termios>, size: Option<&libc::winsize>) -> io::Result<(OwnedFd, OwnedFd)> {
+    let (mut master, mut slave) = (-1, -1);
+    let term = term.map_or(std::ptr::null(), |t| t as *const _);
+    let size = size.map_or(std::ptr::null(), |s| s as *const _);
+    // SAFETY: the out-pointers are valid; libc only reads `term` and `size`
+    if unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), term as _, size as _) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty returned two fds that nothing else owns
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    // Neither may leak into the program, or what it starts, past the stdio
+    // it is given
+    for fd in [&master, &slave] {
+        // SAFETY: `fd` is open
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((master, slave))
+}
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_winch(_: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// Our SIGWINCH handler until dropped, so the relay can pass a resize of our
+/// terminal on to the program's.
+struct WinchHandler {
+    previous: libc::sigaction,
+}
+
+impl WinchHandler {
+    fn install() -> Self {
+        // SAFETY: the handler only stores to an atomic; `previous` is filled
+        // by sigaction before it is read
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_winch as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGWINCH, &action, &mut previous);
+            WinchHandler { previous }
+        }
+    }
+}
+
+impl Drop for WinchHandler {
+    fn drop(&mut self) {
+        // SAFETY: puts back the handler replaced by `install`
+        unsafe {
+            libc::sigaction(libc::SIGWINCH, &self.previous, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Our terminal in raw mode until dropped.
+struct RawMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enter(fd: RawFd, saved: libc::termios) -> Self {
+        let mut raw = saved;
+        // SAFETY: `raw` is a valid termios
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(fd, libc::TCSANOW, &raw);
+        }
+        RawMode { fd, saved }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read before entering raw mode
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+/// Copy stdin to the master and the master to stdout until the program
+/// exits or closes its terminal, and return what it wrote and its status.
+/// One thread polling both, so nothing is left reading stdin once the
+/// program is done.
+fn relay(stdin_fd: RawFd, master: OwnedFd, child: &mut Child) -> io::Result<Outcome> {
+    let mut master = File::from(master);
+    let mut output = Vec::new();
+    let mut buf = [0u8; 4096];
+    let mut stdin_open = true;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            drain(&mut master, &mut output)?;
+            return Ok(Outcome { status, output });
+        }
+        if RESIZED.swap(false, Ordering::Relaxed) {
+            if let Some(size) = window_size(stdin_fd) {
+                // SAFETY: TIOCSWINSZ only reads `size`
+                unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+            }
+        }
+
+        let mut fds = [
+            libc::pollfd { fd: master.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: if stdin_open { stdin_fd } else { -1 }, events: libc::POLLIN, revents: 0 },
+        ];
+        // SAFETY: `fds` is a valid array of two pollfds
+        if unsafe { libc::poll(fds.as_mut_ptr(), 2, EXIT_POLL_MS) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+
+        if fds[0].revents != 0 {
+            // EIO (Linux) or 0 (macOS) once the program has closed its side
+            let n = match master.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            show(&buf[..n], &mut output)?;
+        }
+
+        if fds[1].revents != 0 {
+            // SAFETY: reads into `buf`, which outlives the call
+            let n = unsafe { libc::read(stdin_fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                stdin_open = false;
+            } else {
+                master.write_all(&buf[..n as usize])?;
+            }
+        }
+    }
+    let status = child.wait()?;
+    Ok(Outcome { status, output })
+}
+
+/// Print what the program wrote and keep it.
+fn show(bytes: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(bytes)?;
+    stdout.flush()?;
+    output.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Read what the program left in the terminal before it exited, without
+/// waiting for whoever else may still hold it.
+fn drain(master: &mut File, output: &mut Vec<u8>) -> io::Result<()> {
+    let fd = master.as_raw_fd();
+    // SAFETY: `fd` is open; only O_NONBLOCK is added to its flags
+    unsafe { libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK) };
+    let mut buf = [0u8; 4096];
+    // EAGAIN once it is empty, EIO or 0 once every holder has closed it
+    while let Ok(n @ 1..) = master.read(&mut buf) {
+        show(&buf[..n], output)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// On-disk format versioning for profiles.json and the credential stores.
+//
+// Each store keeps a list of migrations; entry `i` upgrades a file from
+// version `i` to `i + 1`, so the current version is the list's length. Files
+// carry `version` and `written_by` (the cswitch release that wrote them).
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CswitchError, Result};
+use crate::fsutil;
+
+pub type Migration = fn(&mut Value) -> Result<()>;
+
+const VERSION_KEY: &str = "version";
+const WRITTEN_BY_KEY: &str = "written_by";
+
+/// Migration for files written before versioning existed: nothing changes
+/// besides the version stamp.
+pub fn unversioned_to_v1(_: &mut Value) -> Result<()> {
+    Ok(())
+}
+
+fn version_of(value: &Value) -> u64 {
+    value.get(VERSION_KEY).and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Whether `value` is older than `migrations` know. Fails if it's newer.
+fn needs_upgrade(path: &Path, value: &Value, migrations: &[Migration]) -> Result<bool> {
+    let version = version_of(value);
+    let current = migrations.len() as u64;
+    if version > current {
+        let written_by = value
+            .get(WRITTEN_BY_KEY)
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        return Err(CswitchError::NewerSchema {
+            file: file_name(path),
+            version,
+            written_by,
+        });
+    }
+    Ok(version < current)
+}
+
+fn backup_path(path: &Path, version: u64) -> PathBuf {
+    path.with_file_name(format!("{}.v{version}.bak", file_name(path)))
+}
+
+/// Mark `value` as written by this cswitch at the current version.
+pub fn stamp(value: &mut Value, migrations: &[Migration]) {
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert(VERSION_KEY.into(), Value::from(migrations.len() as u64));
+        obj.insert(
+            WRITTEN_BY_KEY.into(),
+            Value::String(env!("CARGO_PKG_VERSION").to_string()),
+        );
+    }
+}
+
+/// Read a store through `read`, upgrading it on disk first if it's older than
+/// `migrations`. The previous file is copied to `<file>.v<N>.bak` before the
+/// upgraded one is saved through `write`. Returns `None` if `path` is missing.
+pub fn load<R, W>(path: &Path, migrations: &[Migration], read: R, write: W) -> Result<Option<Value>>
+where
+    R: Fn() -> Result<Value>,
+    W: FnOnce(&Value) -> Result<()>,
+{
+    if !path.exists() {
+        return Ok(None);
+    }
+    let value = read()?;
+    if !needs_upgrade(path, &value, migrations)? {
+        return Ok(Some(value));
+    }
+
+    // Re-read under the lock so concurrent processes upgrade once
+    let _lock = fsutil::lock(path)?;
+    let mut value = read()?;
+    if needs_upgrade(path, &value, migrations)? {
+        let from = version_of(&value);
+        let backup = backup_path(path, from);
+        fs::copy(path, &backup).map_err(|e| {
+            CswitchError::Config(format!("Failed to back up {} before upgrading: {e}", path.display()))
+        })?;
+        for migration in &migrations[from as usize..] {
+            migration(&mut value)?;
+        }
+        stamp(&mut value, migrations);
+        write(&value)?;
+    }
+    Ok(Some(value))
+}
@@ -0,0 +1,391 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use dialoguer::Password;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::IsTerminal;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::file::{cswitch_dir, from_json, to_json, CredentialStore, MIGRATIONS};
+use super::{SecretBackend, SecretKind};
+use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::schema;
+
+pub const PASSPHRASE_ENV: &str = "CSWITCH_PASSPHRASE";
+
+// MAGIC | 1 | m_cost | t_cost | p_cost | salt | SHA-256 of the preceding bytes
+//       | check nonce | check tag | nonce | ciphertext+tag
+// The digest tells a damaged KDF header apart from a bad passphrase. The
+// check block is an empty message sealed with the derived key and the KDF
+// header as associated data, so a bad passphrase can be told apart from a
+// damaged payload. The whole header is associated data of the payload.
+const MAGIC: &[u8] = b"CSWENC";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const DIGEST_LEN: usize = 32;
+/// Magic, version, KDF parameters and salt: what the key depends on.
+const KDF_HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN;
+const HEADER_LEN: usize = KDF_HEADER_LEN + DIGEST_LEN + NONCE_LEN + TAG_LEN + NONCE_LEN;
+
+/// The most memory (KiB), passes and lanes a file may ask Argon2 for, so a
+/// damaged or tampered header can't make unlocking allocate gigabytes or spin.
+const MAX_M_COST: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+
+const KEY_CHECK_AAD: &[u8] = b"cswitch key check";
+const MAX_PROMPT_ATTEMPTS: usize = 3;
+
+/// Passphrase for this process, asked for at most once per successful unlock.
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+pub(super) fn encrypted_path() -> Result<PathBuf> {
+    Ok(cswitch_dir()?.join("credentials.enc"))
+}
+
+fn cached_passphrase() -> Option<String> {
+    PASSPHRASE.lock().ok().and_then(|p| p.clone())
+}
+
+fn remember_passphrase(passphrase: &str) {
+    if let Ok(mut cached) = PASSPHRASE.lock() {
+        *cached = Some(passphrase.to_string());
+    }
+}
+
+fn forget_passphrase() {
+    if let Ok(mut cached) = PASSPHRASE.lock() {
+        *cached = None;
+    }
+}
+
+fn env_passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())
+}
+
+/// Ask for a passphrase on the terminal. `confirm` asks twice, for new files.
+pub fn prompt_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    // `emit-key` runs under Claude Code with no terminal to prompt on
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Err(CswitchError::Keychain(format!(
+            "Credentials are encrypted and no terminal is available. Set {PASSPHRASE_ENV} to unlock them."
+        )));
+    }
+    let mut input = Password::new().with_prompt(prompt);
+    if confirm {
+        input = input.with_confirmation("Confirm passphrase", "Passphrases don't match");
+    }
+    input
+        .interact()
+        .map_err(|e| CswitchError::Keychain(format!("Passphrase input error: {e}")))
+}
+
+fn kdf_params() -> Params {
+    Params::default()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: Params) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CswitchError::Keychain(format!("Key derivation failed: {e}")))?;
+    Ok(key)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The Argon2 parameters stored in a header, if they are within bounds.
+fn read_params(bytes: &[u8]) -> Result<Params> {
+    let (m_cost, t_cost, p_cost) = (read_u32(&bytes[0..4]), read_u32(&bytes[4..8]), read_u32(&bytes[8..12]));
+    if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+        return Err(CswitchError::CorruptedCredentials(format!(
+            "key derivation parameters out of range (m={m_cost}, t={t_cost}, p={p_cost})"
+        )));
+    }
+    Params::new(m_cost, t_cost, p_cost, None)
+        .map_err(|e| CswitchError::CorruptedCredentials(format!("bad key derivation parameters: {e}")))
+}
+
+/// Associated data of the key check: the KDF header it was derived with.
+fn key_check_aad(kdf_header: &[u8]) -> Vec<u8> {
+    [KEY_CHECK_AAD, kdf_header].concat()
+}
+
+/// Decrypt an encrypted credentials file with `passphrase`.
+fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if data.len() <= MAGIC.len() || !data.starts_with(MAGIC) {
+        return Err(CswitchError::CorruptedCredentials(
+            "not a cswitch encrypted credentials file".into(),
+        ));
+    }
+    let version = data[MAGIC.len()];
+    if version != VERSION {
+        return Err(CswitchError::CorruptedCredentials(format!(
+            "unsupported format version {version} (written by a newer cswitch?)"
+        )));
+    }
+    if data.len() < HEADER_LEN + TAG_LEN {
+        return Err(CswitchError::CorruptedCredentials("file is truncated".into()));
+    }
+    let kdf_header = &data[..KDF_HEADER_LEN];
+    let (digest, rest) = data[KDF_HEADER_LEN..].split_at(DIGEST_LEN);
+    if Sha256::digest(kdf_header).as_slice() != digest {
+        return Err(CswitchError::CorruptedCredentials("header is damaged".into()));
+    }
+    let body = &kdf_header[MAGIC.len() + 1..];
+    let params = read_params(&body[..12])?;
+    let salt = &body[12..];
+    let (check_nonce, rest) = rest.split_at(NONCE_LEN);
+    let (check_tag, rest) = rest.split_at(TAG_LEN);
+    let nonce = &rest[..NONCE_LEN];
+
+    let key = derive_key(passphrase, salt, params)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    cipher
+        .decrypt(
+            XNonce::from_slice(check_nonce),
+            Payload { msg: check_tag, aad: &key_check_aad(kdf_header) },
+        )
+        .map_err(|_| CswitchError::WrongPassphrase)?;
+    cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload { msg: &data[HEADER_LEN..], aad: &data[..HEADER_LEN] },
+        )
+        .map_err(|_| CswitchError::CorruptedCredentials("payload failed authentication".into()))
+}
+
+/// Encrypt `plaintext` into the current file format.
+fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let params = kdf_params();
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, params.clone())?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+
+    let mut data = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.extend_from_slice(&params.m_cost().to_le_bytes());
+    data.extend_from_slice(&params.t_cost().to_le_bytes());
+    data.extend_from_slice(&params.p_cost().to_le_bytes());
+    data.extend_from_slice(&salt);
+    let digest = Sha256::digest(&data);
+
+    let check_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let check_tag = cipher
+        .encrypt(&check_nonce, Payload { msg: &[], aad: &key_check_aad(&data) })
+        .map_err(|_| CswitchError::Keychain("Failed to encrypt credentials".into()))?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    data.extend_from_slice(&digest);
+    data.extend_from_slice(&check_nonce);
+    data.extend_from_slice(&check_tag);
+    data.extend_from_slice(&nonce);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad: &data })
+        .map_err(|_| CswitchError::Keychain("Failed to encrypt credentials".into()))?;
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// Decrypt `data`, asking for the passphrase if it isn't known yet.
+/// Interactive prompts get a few attempts; `CSWITCH_PASSPHRASE` gets one.
+fn unlock(data: &[u8]) -> Result<Vec<u8>> {
+    if let Some(passphrase) = cached_passphrase().or_else(env_passphrase) {
+        let plaintext = decrypt(data, &passphrase)?;
+        remember_passphrase(&passphrase);
+        return Ok(plaintext);
+    }
+
+    let mut attempt = 1;
+    loop {
+        let passphrase = prompt_passphrase("cswitch passphrase", false)?;
+        match decrypt(data, &passphrase) {
+            Ok(plaintext) => {
+                remember_passphrase(&passphrase);
+                return Ok(plaintext);
+            }
+            Err(CswitchError::WrongPassphrase) if attempt < MAX_PROMPT_ATTEMPTS => {
+                eprintln!("Wrong passphrase, try again.");
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let data = fs::read(path)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to read credentials: {e}")))?;
+    let plaintext = unlock(&data)?;
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| CswitchError::CorruptedCredentials(format!("decrypted data is not valid JSON: {e}")))
+}
+
+fn write_json(path: &Path, value: &Value, passphrase: &str) -> Result<()> {
+    let plaintext = serde_json::to_vec(value)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to serialize credentials: {e}")))?;
+    let data = encrypt(&plaintext, passphrase)?;
+    fsutil::write_private_atomic(path, &data)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to write credentials: {e}")))?;
+    remember_passphrase(passphrase);
+    Ok(())
+}
+
+fn current_passphrase() -> Result<String> {
+    match cached_passphrase().or_else(env_passphrase) {
+        Some(p) => Ok(p),
+        None => prompt_passphrase("New cswitch passphrase", true),
+    }
+}
+
+fn load_store() -> Result<CredentialStore> {
+    let path = encrypted_path()?;
+    let value = schema::load(
+        &path,
+        MIGRATIONS,
+        || read_json(&path),
+        |v| write_json(&path, v, &current_passphrase()?),
+    )?;
+    match value {
+        Some(value) => from_json(value),
+        None => Ok(CredentialStore::default()),
+    }
+}
+
+fn save_store(store: &CredentialStore) -> Result<()> {
+    write_store(store, &current_passphrase()?)
+}
+
+fn write_store(store: &CredentialStore, passphrase: &str) -> Result<()> {
+    write_json(&encrypted_path()?, &to_json(store)?, passphrase)
+}
+
+/// Reload, modify and save the store while holding its lock.
+fn update_store(f: impl FnOnce(&mut CredentialStore) -> bool) -> Result<()> {
+    let _lock = fsutil::lock(&encrypted_path()?)?;
+    let mut store = load_store()?;
+    if f(&mut store) {
+        save_store(&store)?;
+    }
+    Ok(())
+}
+
+/// Unlock the store with the current passphrase, then re-encrypt it under a new one.
+pub fn change_passphrase() -> Result<()> {
+    let _lock = fsutil::lock(&encrypted_path()?)?;
+    let store = load_store()?;
+    let new_passphrase = prompt_passphrase("New cswitch passphrase", true)?;
+    forget_passphrase();
+    write_store(&store, &new_passphrase)
+}
+
+/// `~/.config/cswitch/credentials.enc`: the file store encrypted with a
+/// passphrase (Argon2id + XChaCha20-Poly1305).
+pub struct EncryptedFileBackend;
+
+impl SecretBackend for EncryptedFileBackend {
+    fn get(&self, kind: SecretKind, profile: &str) -> Result<Option<String>> {
+        Ok(load_store()?.entries(kind).get(profile).cloned())
+    }
+
+    fn set(&self, kind: SecretKind, profile: &str, secret: &str) -> Result<()> {
+        update_store(|store| {
+            store.entries_mut(kind).insert(profile.to_string(), secret.to_string());
+            true
+        })
+    }
+
+    fn delete(&self, kind: SecretKind, profile: &str) -> Result<()> {
+        update_store(|store| store.entries_mut(kind).remove(profile).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAINTEXT: &[u8] = br#"{"api_keys":{"work":"sk-ant-api03-x"}}"#;
+
+    fn corrupted(result: Result<Vec<u8>>) -> String {
+        match result {
+            Err(CswitchError::CorruptedCredentials(reason)) => reason,
+            other => panic!("expected a corrupted file, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let data = encrypt(PLAINTEXT, "correct horse").unwrap();
+        assert!(data.starts_with(MAGIC));
+        assert_eq!(data[MAGIC.len()], VERSION);
+        assert_eq!(data.len(), HEADER_LEN + PLAINTEXT.len() + TAG_LEN);
+        assert_eq!(decrypt(&data, "correct horse").unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn salts_each_file() {
+        let a = encrypt(PLAINTEXT, "pw").unwrap();
+        let b = encrypt(PLAINTEXT, "pw").unwrap();
+        assert_ne!(a[..KDF_HEADER_LEN], b[..KDF_HEADER_LEN]);
+        assert_ne!(a[HEADER_LEN..], b[HEADER_LEN..]);
+    }
+
+    #[test]
+    fn tells_a_wrong_passphrase() {
+        let data = encrypt(PLAINTEXT, "correct horse").unwrap();
+        assert!(matches!(decrypt(&data, "battery staple"), Err(CswitchError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn tells_a_damaged_header() {
+        let data = encrypt(PLAINTEXT, "pw").unwrap();
+        // m_cost, t_cost, the last salt byte and the digest itself
+        for i in [MAGIC.len() + 1, MAGIC.len() + 5, KDF_HEADER_LEN - 1, KDF_HEADER_LEN] {
+            let mut damaged = data.clone();
+            damaged[i] ^= 0x01;
+            assert_eq!(corrupted(decrypt(&damaged, "pw")), "header is damaged");
+        }
+    }
+
+    #[test]
+    fn tells_a_damaged_payload() {
+        let mut data = encrypt(PLAINTEXT, "pw").unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0x01;
+        assert_eq!(corrupted(decrypt(&data, "pw")), "payload failed authentication");
+    }
+
+    #[test]
+    fn refuses_out_of_range_parameters() {
+        let mut data = encrypt(PLAINTEXT, "pw").unwrap();
+        let m_cost = MAGIC.len() + 1;
+        data[m_cost..m_cost + 4].copy_from_slice(&(MAX_M_COST + 1).to_le_bytes());
+        // A consistent digest, as a tampered file would carry
+        let digest = Sha256::digest(&data[..KDF_HEADER_LEN]);
+        data[KDF_HEADER_LEN..KDF_HEADER_LEN + DIGEST_LEN].copy_from_slice(&digest);
+        assert!(corrupted(decrypt(&data, "pw")).contains("out of range"));
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        let data = encrypt(PLAINTEXT, "pw").unwrap();
+        assert!(corrupted(decrypt(b"{}", "pw")).contains("not a cswitch"));
+        assert!(corrupted(decrypt(&data[..HEADER_LEN], "pw")).contains("truncated"));
+
+        let mut newer = data.clone();
+        newer[MAGIC.len()] = VERSION + 1;
+        assert!(corrupted(decrypt(&newer, "pw")).contains("unsupported format version"));
+    }
+}
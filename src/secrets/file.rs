@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::{SecretBackend, SecretKind};
+use crate::error::{CswitchError, Result};
+use crate::fsutil;
+use crate::schema::{self, Migration};
+
+/// All profile secrets, keyed by profile name. Shared by the file-based backends.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct CredentialStore {
+    api_keys: HashMap<String, String>,
+    oauth_tokens: HashMap<String, String>,
+    #[serde(default)]
+    auth_tokens: HashMap<String, String>,
+    #[serde(default)]
+    setup_tokens: HashMap<String, String>,
+}
+
+impl CredentialStore {
+    pub(super) fn entries(&self, kind: SecretKind) -> &HashMap<String, String> {
+        match kind {
+            SecretKind::ApiKey => &self.api_keys,
+            SecretKind::OAuthToken => &self.oauth_tokens,
+            SecretKind::AuthToken => &self.auth_tokens,
+            SecretKind::SetupToken => &self.setup_tokens,
+        }
+    }
+
+    pub(super) fn entries_mut(&mut self, kind: SecretKind) -> &mut HashMap<String, String> {
+        match kind {
+            SecretKind::ApiKey => &mut self.api_keys,
+            SecretKind::OAuthToken => &mut self.oauth_tokens,
+            SecretKind::AuthToken => &mut self.auth_tokens,
+            SecretKind::SetupToken => &mut self.setup_tokens,
+        }
+    }
+}
+
+pub(super) fn cswitch_dir() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir()
+        .ok_or_else(|| CswitchError::Keychain("Cannot determine config directory".into()))?;
+    Ok(config_dir.join("cswitch"))
+}
+
+pub(super) fn credentials_path() -> Result<PathBuf> {
+    Ok(cswitch_dir()?.join("credentials.json"))
+}
+
+/// Upgrades for the credential store, oldest first (see `schema`). Shared by
+/// the plaintext and encrypted files, which hold the same JSON.
+pub(super) const MIGRATIONS: &[Migration] = &[schema::unversioned_to_v1];
+
+/// Serialize the store with its version stamp.
+pub(super) fn to_json(store: &CredentialStore) -> Result<Value> {
+    let mut value = serde_json::to_value(store)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to serialize credentials: {e}")))?;
+    schema::stamp(&mut value, MIGRATIONS);
+    Ok(value)
+}
+
+pub(super) fn from_json(value: Value) -> Result<CredentialStore> {
+    serde_json::from_value(value).map_err(|e| CswitchError::Keychain(format!("Invalid credentials file: {e}")))
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to read credentials: {e}")))?;
+    serde_json::from_str(&data).map_err(|e| CswitchError::Keychain(format!("Invalid credentials file: {e}")))
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    let data = serde_json::to_string_pretty(value)
+        .map_err(|e| CswitchError::Keychain(format!("Failed to serialize credentials: {e}")))?;
+    // Owner only (600), replaced atomically
+    fsutil::write_private_atomic(path, data.as_bytes())
+        .map_err(|e| CswitchError::Keychain(format!("Failed to write credentials: {e}")))
+}
+
+fn load_store() -> Result<CredentialStore> {
+    let path = credentials_path()?;
+    let value = schema::load(&path, MIGRATIONS, || read_json(&path), |v| write_json(&path, v))?;
+    match value {
+        Some(value) => from_json(value),
+        None => Ok(CredentialStore::default()),
+    }
+}
+
+fn save_store(store: &CredentialStore) -> Result<()> {
+    write_json(&credentials_path()?, &to_json(store)?)
+}
+
+/// Reload, modify and save the store while holding its lock.
+fn update_store(f: impl FnOnce(&mut CredentialStore) -> bool) -> Result<()> {
+    let _lock = fsutil::lock(&credentials_path()?)?;
+    let mut store = load_store()?;
+    if f(&mut store) {
+        save_store(&store)?;
+    }
+    Ok(())
+}
+
+/// Plaintext `~/.config/cswitch/credentials.json`, readable by the owner only.
+pub struct FileBackend;
+
+impl SecretBackend for FileBackend {
+    fn get(&self, kind: SecretKind, profile: &str) -> Result<Option<String>> {
+        Ok(load_store()?.entries(kind).get(profile).cloned())
+    }
+
+    fn set(&self, kind: SecretKind, profile: &str, secret: &str) -> Result<()> {
+        update_store(|store| {
+            store.entries_mut(kind).insert(profile.to_string(), secret.to_string());
+            true
+        })
+    }
+
+    fn delete(&self, kind: SecretKind, profile: &str) -> Result<()> {
+        update_store(|store| store.entries_mut(kind).remove(profile).is_some())
+    }
+}
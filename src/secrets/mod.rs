@@ -0,0 +1,113 @@
+// Pluggable storage for profile secrets (API keys, OAuth tokens, gateway
+// bearer tokens, setup tokens).
+//
+// `keychain` exposes the free functions the commands use; they dispatch to
+// whichever backend the install is configured with in `config.json`.
+
+mod encrypted;
+mod file;
+pub mod reference;
+mod secret_service;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+
+pub use encrypted::{change_passphrase, EncryptedFileBackend};
+pub use file::FileBackend;
+pub use secret_service::SecretServiceBackend;
+
+/// The kinds of secret a profile can own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    ApiKey,
+    OAuthToken,
+    AuthToken,
+    SetupToken,
+}
+
+impl SecretKind {
+    pub const ALL: [SecretKind; 4] = [
+        SecretKind::ApiKey,
+        SecretKind::OAuthToken,
+        SecretKind::AuthToken,
+        SecretKind::SetupToken,
+    ];
+
+    /// Stable identifier used as a storage attribute.
+    pub fn id(&self) -> &'static str {
+        match self {
+            SecretKind::ApiKey => "api-key",
+            SecretKind::OAuthToken => "oauth-token",
+            SecretKind::AuthToken => "auth-token",
+            SecretKind::SetupToken => "setup-token",
+        }
+    }
+}
+
+impl std::fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretKind::ApiKey => write!(f, "API key"),
+            SecretKind::OAuthToken => write!(f, "OAuth token"),
+            SecretKind::AuthToken => write!(f, "auth token"),
+            SecretKind::SetupToken => write!(f, "setup token"),
+        }
+    }
+}
+
+/// A place profile secrets can be stored.
+pub trait SecretBackend {
+    /// Return the secret, or `None` if the profile has none of this kind.
+    fn get(&self, kind: SecretKind, profile: &str) -> Result<Option<String>>;
+
+    fn set(&self, kind: SecretKind, profile: &str, secret: &str) -> Result<()>;
+
+    /// Remove the secret. Removing a missing secret is not an error.
+    fn delete(&self, kind: SecretKind, profile: &str) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// Plaintext `credentials.json` (mode 600)
+    #[default]
+    File,
+    /// Passphrase-encrypted `credentials.enc`
+    EncryptedFile,
+    /// Desktop keyring through the freedesktop Secret Service (`secret-tool`)
+    SecretService,
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendKind::File => write!(f, "file"),
+            BackendKind::EncryptedFile => write!(f, "encrypted-file"),
+            BackendKind::SecretService => write!(f, "secret-service"),
+        }
+    }
+}
+
+pub fn open(kind: BackendKind) -> Box<dyn SecretBackend> {
+    match kind {
+        BackendKind::File => Box::new(FileBackend),
+        BackendKind::EncryptedFile => Box::new(EncryptedFileBackend),
+        BackendKind::SecretService => Box::new(SecretServiceBackend),
+    }
+}
+
+/// The local files secrets may be kept in, whichever exist.
+pub fn credential_files() -> Result<Vec<std::path::PathBuf>> {
+    Ok([file::credentials_path()?, encrypted::encrypted_path()?]
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect())
+}
+
+/// Open the backend this install is configured to use.
+pub fn active() -> Result<Box<dyn SecretBackend>> {
+    Ok(open(Config::load()?.secret_backend))
+}
@@ -0,0 +1,126 @@
+// Secret references: a profile can store `env:VAR`, `file:/path` or
+// `cmd:<shell command>` instead of the secret itself, resolved at use time.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::{CswitchError, Result};
+
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 10;
+
+/// Whether a stored value is a reference rather than a literal secret.
+pub fn is_reference(value: &str) -> bool {
+    ["env:", "file:", "cmd:"].iter().any(|p| value.starts_with(p))
+}
+
+/// Return the secret a stored value stands for. Literals are returned as-is.
+pub fn resolve(value: &str) -> Result<String> {
+    if let Some(var) = value.strip_prefix("env:") {
+        resolve_env(var.trim())
+    } else if let Some(path) = value.strip_prefix("file:") {
+        resolve_file(path.trim())
+    } else if let Some(cmd) = value.strip_prefix("cmd:") {
+        resolve_cmd(cmd.trim())
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn non_empty(secret: String, source: &str) -> Result<String> {
+    let secret = secret.trim().to_string();
+    if secret.is_empty() {
+        return Err(CswitchError::SecretReference(format!("{source} is empty")));
+    }
+    Ok(secret)
+}
+
+fn resolve_env(var: &str) -> Result<String> {
+    let value = std::env::var(var).map_err(|_| {
+        CswitchError::SecretReference(format!("environment variable {var} is not set"))
+    })?;
+    non_empty(value, &format!("environment variable {var}"))
+}
+
+fn resolve_file(path: &str) -> Result<String> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .ok_or_else(|| CswitchError::SecretReference("Cannot determine home directory".into()))?
+            .join(rest),
+        None => path.into(),
+    };
+    let value = std::fs::read_to_string(&path)
+        .map_err(|e| CswitchError::SecretReference(format!("cannot read {}: {e}", path.display())))?;
+    non_empty(value, &path.display().to_string())
+}
+
+fn resolve_cmd(cmd: &str) -> Result<String> {
+    let timeout = Duration::from_secs(
+        Config::load()?
+            .secret_command_timeout_secs
+            .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS),
+    );
+
+    let mut child = Command::new("sh")
+        .args(["-c", cmd])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CswitchError::SecretReference(format!("failed to run `{cmd}`: {e}")))?;
+
+    // Drain the pipes on threads so a chatty command can't block on a full pipe
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let out_reader = std::thread::spawn(move || {
+        let mut buf = String::new();
+        if let Some(out) = stdout.as_mut() {
+            let _ = out.read_to_string(&mut buf);
+        }
+        buf
+    });
+    let err_reader = std::thread::spawn(move || {
+        let mut buf = String::new();
+        if let Some(err) = stderr.as_mut() {
+            let _ = err.read_to_string(&mut buf);
+        }
+        buf
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| CswitchError::SecretReference(format!("failed to wait for `{cmd}`: {e}")))?
+        {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CswitchError::SecretReference(format!(
+                "`{cmd}` timed out after {}s",
+                timeout.as_secs()
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    let stdout = out_reader.join().unwrap_or_default();
+    let stderr = err_reader.join().unwrap_or_default();
+
+    if !status.success() {
+        let code = status
+            .code()
+            .map(|c| format!("exit code {c}"))
+            .unwrap_or_else(|| "a signal".to_string());
+        let detail = stderr.trim();
+        return Err(CswitchError::SecretReference(if detail.is_empty() {
+            format!("`{cmd}` failed with {code}")
+        } else {
+            format!("`{cmd}` failed with {code}: {detail}")
+        }));
+    }
+    non_empty(stdout, &format!("output of `{cmd}`"))
+}
@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::{SecretBackend, SecretKind};
+use crate::error::{CswitchError, Result};
+
+const SERVICE: &str = "cswitch";
+
+/// The desktop keyring (GNOME Keyring, KWallet, KeePassXC...) reached through
+/// `secret-tool` from libsecret. Each secret is an item tagged with
+/// `service=cswitch kind=<kind> profile=<name>`.
+pub struct SecretServiceBackend;
+
+fn attributes(kind: SecretKind, profile: &str) -> [&str; 6] {
+    ["service", SERVICE, "kind", kind.id(), "profile", profile]
+}
+
+fn secret_tool() -> Command {
+    Command::new("secret-tool")
+}
+
+fn spawn_error(e: std::io::Error) -> CswitchError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        CswitchError::Keychain("secret-tool not found. Install libsecret-tools (or libsecret).".into())
+    } else {
+        CswitchError::Keychain(format!("Failed to run secret-tool: {e}"))
+    }
+}
+
+impl SecretBackend for SecretServiceBackend {
+    fn get(&self, kind: SecretKind, profile: &str) -> Result<Option<String>> {
+        let output = secret_tool()
+            .arg("lookup")
+            .args(attributes(kind, profile))
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(spawn_error)?;
+
+        // secret-tool exits with 1 and prints nothing when nothing matches.
+        // Anything else (a locked keyring, no D-Bus session, a crashed
+        // daemon) is not the same as having no secret; those also exit with
+        // 1, but say why on stderr.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        if output.status.code() == Some(1) && output.stdout.is_empty() && stderr.is_empty() {
+            return Ok(None);
+        }
+        if !output.status.success() {
+            let reason = if stderr.is_empty() { output.status.to_string() } else { stderr.to_string() };
+            return Err(CswitchError::Keychain(format!(
+                "secret-tool could not look up the {kind}: {reason}"
+            )));
+        }
+        if output.stdout.is_empty() {
+            return Ok(None);
+        }
+        String::from_utf8(output.stdout)
+            .map(Some)
+            .map_err(|e| CswitchError::Keychain(format!("Invalid secret encoding: {e}")))
+    }
+
+    fn set(&self, kind: SecretKind, profile: &str, secret: &str) -> Result<()> {
+        // The secret goes through stdin so it never shows up in the process list
+        let mut child = secret_tool()
+            .arg("store")
+            .arg(format!("--label=cswitch {kind} ({profile})"))
+            .args(attributes(kind, profile))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(secret.as_bytes())
+                .map_err(|e| CswitchError::Keychain(format!("Failed to pass secret to secret-tool: {e}")))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| CswitchError::Keychain(format!("Failed to run secret-tool: {e}")))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CswitchError::Keychain(format!(
+                "secret-tool could not store the {kind}: {}",
+                stderr.trim()
+            )));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, kind: SecretKind, profile: &str) -> Result<()> {
+        let output = secret_tool()
+            .arg("clear")
+            .args(attributes(kind, profile))
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(spawn_error)?;
+
+        // As with `lookup`, a silent exit 1 means nothing matched, which
+        // is fine here; a failure says why on stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        if output.status.success() || (output.status.code() == Some(1) && stderr.is_empty()) {
+            return Ok(());
+        }
+        let reason = if stderr.is_empty() { output.status.to_string() } else { stderr.to_string() };
+        Err(CswitchError::Keychain(format!(
+            "secret-tool could not delete the {kind}: {reason}"
+        )))
+    }
+}
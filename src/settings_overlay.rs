@@ -0,0 +1,285 @@
+// Per-profile settings overlays: a JSON object deep-merged into Claude Code's
+// settings.json on `use`. What gets written is recorded leaf by leaf, so
+// switching away undoes exactly that and nothing the user changed since.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Top-level keys captured when none are named.
+pub const DEFAULT_KEYS: &[&str] = &["model", "permissions", "hooks"];
+
+/// Keys cswitch manages on its own; an overlay never holds them.
+pub const RESERVED_KEYS: &[&str] = &["apiKeyHelper", "env"];
+
+/// One value an overlay wrote into settings.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayEntry {
+    pub path: Vec<String>,
+    pub value: Value,
+    /// What was at `path` before, restored when the overlay is removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Value>,
+}
+
+/// Deep-merge `overlay` into `settings` and return what was written. Objects
+/// merge key by key; anything else (arrays included) replaces the old value.
+pub fn merge(settings: &mut Map<String, Value>, overlay: &Map<String, Value>) -> Vec<OverlayEntry> {
+    let mut entries = Vec::new();
+    merge_at(settings, overlay, &mut Vec::new(), &mut entries);
+    entries
+}
+
+fn merge_at(
+    target: &mut Map<String, Value>,
+    overlay: &Map<String, Value>,
+    path: &mut Vec<String>,
+    entries: &mut Vec<OverlayEntry>,
+) {
+    for (key, value) in overlay {
+        path.push(key.clone());
+        match (target.get_mut(key), value) {
+            (Some(Value::Object(inner)), Value::Object(sub)) => merge_at(inner, sub, path, entries),
+            // Already set to this value by the user: not ours to remove later
+            (Some(existing), _) if existing == value => {}
+            (existing, _) => {
+                entries.push(OverlayEntry {
+                    path: path.clone(),
+                    value: value.clone(),
+                    previous: existing.cloned(),
+                });
+                target.insert(key.clone(), value.clone());
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Undo `entries`, newest first. A value changed since it was written now
+/// belongs to the user and is kept. Objects left empty are removed.
+pub fn revert(settings: &mut Map<String, Value>, entries: &[OverlayEntry]) {
+    for entry in entries.iter().rev() {
+        let Some((key, parents)) = entry.path.split_last() else {
+            continue;
+        };
+        let Some(parent) = object_at(settings, parents) else {
+            continue;
+        };
+        if parent.get(key) != Some(&entry.value) {
+            continue;
+        }
+        match &entry.previous {
+            Some(previous) => parent.insert(key.clone(), previous.clone()),
+            None => parent.shift_remove(key),
+        };
+        prune(settings, parents);
+    }
+}
+
+fn object_at<'a>(obj: &'a mut Map<String, Value>, path: &[String]) -> Option<&'a mut Map<String, Value>> {
+    path.iter().try_fold(obj, |obj, key| obj.get_mut(key)?.as_object_mut())
+}
+
+/// Remove the objects along `path` that are empty, deepest first.
+fn prune(settings: &mut Map<String, Value>, path: &[String]) {
+    for len in (1..=path.len()).rev() {
+        let (key, parents) = path[..len].split_last().expect("len >= 1");
+        let Some(parent) = object_at(settings, parents) else {
+            return;
+        };
+        if !parent.get(key).and_then(Value::as_object).is_some_and(Map::is_empty) {
+            return;
+        }
+        parent.shift_remove(key);
+    }
+}
+
+/// Take over the current value of top-level `key` (or its absence) as part
+/// of an overlay that is already in `settings`: drop the old entries under
+/// it and record the new leaves, keeping what they replaced originally.
+pub fn adopt(entries: &mut Vec<OverlayEntry>, key: &str, value: Option<&Value>) {
+    let (old, kept): (Vec<_>, Vec<_>) = entries.drain(..).partition(|e| e.path[0] == key);
+    *entries = kept;
+
+    let Some(value) = value else {
+        return;
+    };
+    let mut leaves = Vec::new();
+    flatten(value, &mut vec![key.to_string()], &mut leaves);
+    entries.extend(leaves.into_iter().map(|(path, value)| {
+        let previous = old.iter().find(|e| e.path == path).and_then(|e| e.previous.clone());
+        OverlayEntry { path, value, previous }
+    }));
+}
+
+fn flatten(value: &Value, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, Value)>) {
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (key, value) in obj {
+                path.push(key.clone());
+                flatten(value, path, out);
+                path.pop();
+            }
+        }
+        _ => out.push((path.clone(), value.clone())),
+    }
+}
+
+/// A leaf that differs between two settings documents.
+pub struct Change {
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Every leaf that differs between `before` and `after`, in key order.
+pub fn diff(before: &Value, after: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(Some(before), Some(after), &mut Vec::new(), &mut changes);
+    changes
+}
+
+fn diff_at(before: Option<&Value>, after: Option<&Value>, path: &mut Vec<String>, out: &mut Vec<Change>) {
+    if let (Some(Value::Object(b)), Some(Value::Object(a))) = (before, after) {
+        let mut keys: Vec<&String> = b.keys().chain(a.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            path.push(key.clone());
+            diff_at(b.get(key), a.get(key), path, out);
+            path.pop();
+        }
+    } else if before != after {
+        out.push(Change {
+            path: path.join("."),
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(obj) => obj,
+            other => panic!("not an object: {other}"),
+        }
+    }
+
+    #[test]
+    fn merges_deeply_and_reverts() {
+        let original = object(json!({
+            "model": "sonnet",
+            "theme": "dark",
+            "permissions": { "allow": ["Read"], "defaultMode": "default" },
+        }));
+        let overlay = object(json!({
+            "model": "opus",
+            "permissions": { "allow": ["Bash(git:*)"], "deny": ["WebFetch"] },
+            "hooks": { "Stop": [] },
+        }));
+
+        let mut settings = original.clone();
+        let entries = merge(&mut settings, &overlay);
+        assert_eq!(
+            Value::Object(settings.clone()),
+            json!({
+                "model": "opus",
+                "theme": "dark",
+                "permissions": {
+                    "allow": ["Bash(git:*)"],
+                    "defaultMode": "default",
+                    "deny": ["WebFetch"],
+                },
+                "hooks": { "Stop": [] },
+            })
+        );
+        let paths: Vec<String> = entries.iter().map(|e| e.path.join(".")).collect();
+        assert_eq!(paths, ["model", "permissions.allow", "permissions.deny", "hooks"]);
+        assert_eq!(entries[0].previous, Some(json!("sonnet")));
+        assert_eq!(entries[2].previous, None);
+
+        revert(&mut settings, &entries);
+        assert_eq!(settings, original);
+    }
+
+    #[test]
+    fn leaves_values_the_user_already_had() {
+        let mut settings = object(json!({ "model": "opus" }));
+        let entries = merge(&mut settings, &object(json!({ "model": "opus" })));
+        assert!(entries.is_empty());
+        revert(&mut settings, &entries);
+        assert_eq!(Value::Object(settings), json!({ "model": "opus" }));
+    }
+
+    #[test]
+    fn keeps_values_changed_since() {
+        let mut settings = object(json!({ "model": "sonnet" }));
+        let entries = merge(&mut settings, &object(json!({ "model": "opus", "statusLine": "x" })));
+        settings.insert("model".into(), json!("haiku"));
+        revert(&mut settings, &entries);
+        assert_eq!(Value::Object(settings), json!({ "model": "haiku" }));
+    }
+
+    #[test]
+    fn removes_objects_left_empty() {
+        let original = object(json!({ "permissions": { "allow": ["Read"] } }));
+        let mut settings = original.clone();
+        let entries = merge(&mut settings, &object(json!({ "permissions": { "deny": ["Bash"] } })));
+        revert(&mut settings, &entries);
+        assert_eq!(settings, original);
+
+        let mut settings = object(json!({ "theme": "dark", "permissions": {} }));
+        let entries = merge(&mut settings, &object(json!({ "permissions": { "deny": ["Bash"] } })));
+        revert(&mut settings, &entries);
+        assert_eq!(Value::Object(settings), json!({ "theme": "dark" }));
+    }
+
+    #[test]
+    fn reverts_newest_first() {
+        let mut settings = object(json!({ "model": "sonnet" }));
+        let mut entries = merge(&mut settings, &object(json!({ "model": "opus" })));
+        entries.extend(merge(&mut settings, &object(json!({ "model": "haiku" }))));
+        revert(&mut settings, &entries);
+        assert_eq!(Value::Object(settings), json!({ "model": "sonnet" }));
+    }
+
+    #[test]
+    fn adopts_captured_values() {
+        let mut settings = object(json!({ "permissions": { "allow": ["Read"] } }));
+        let mut entries = merge(&mut settings, &object(json!({ "permissions": { "allow": ["Bash"] } })));
+
+        // The user edits the live value, then captures it
+        let captured = json!({ "allow": ["Bash", "Edit"], "deny": [] });
+        settings.insert("permissions".into(), captured.clone());
+        adopt(&mut entries, "permissions", Some(&captured));
+        let paths: Vec<String> = entries.iter().map(|e| e.path.join(".")).collect();
+        assert_eq!(paths, ["permissions.allow", "permissions.deny"]);
+
+        revert(&mut settings, &entries);
+        assert_eq!(Value::Object(settings), json!({ "permissions": { "allow": ["Read"] } }));
+
+        adopt(&mut entries, "permissions", None);
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn diffs_leaves() {
+        let before = json!({ "model": "sonnet", "permissions": { "allow": ["Read"] }, "theme": "dark" });
+        let after = json!({ "model": "opus", "permissions": { "allow": ["Read"], "deny": [] }, "theme": "dark" });
+        let changes: Vec<_> = diff(&before, &after)
+            .into_iter()
+            .map(|c| (c.path, c.before, c.after))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("model".to_string(), Some(json!("sonnet")), Some(json!("opus"))),
+                ("permissions.deny".to_string(), None, Some(json!([]))),
+            ]
+        );
+    }
+}
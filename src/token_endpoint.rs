@@ -0,0 +1,487 @@
+// Claude's OAuth endpoints: the authorize page a browser login starts at,
+// and the token endpoint that exchanges its code, or a refresh token, for an
+// access token. They and the client id default to Claude Code's and can be
+// pointed elsewhere (a mock server, an egress proxy) through config or env.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::{CswitchError, Result};
+
+const DEFAULT_AUTHORIZE_URL: &str = "https://claude.ai/oauth/authorize";
+const DEFAULT_TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+const DEFAULT_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+
+/// Override the authorize page URL (wins over config.json).
+pub const AUTHORIZE_URL_ENV: &str = "CSWITCH_OAUTH_AUTHORIZE_URL";
+/// Override the token endpoint URL (wins over config.json).
+pub const TOKEN_URL_ENV: &str = "CSWITCH_OAUTH_TOKEN_URL";
+/// Override the OAuth client id (wins over config.json).
+pub const CLIENT_ID_ENV: &str = "CSWITCH_OAUTH_CLIENT_ID";
+
+/// Whole-request timeout, connecting included.
+const TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Tries for a request failing with a network error, a 5xx or a 429.
+const ATTEMPTS: u32 = 3;
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+/// The longest `Retry-After` waited out; a later one fails the request.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// OAuth errors (RFC 6749) that refuse the grant or the client for good:
+/// the user has to log in again.
+const REJECTIONS: &[&str] = &["invalid_grant", "invalid_client"];
+
+/// Where logins start and tokens are requested, and as which client.
+pub struct Endpoint {
+    pub authorize_url: String,
+    pub url: String,
+    pub client_id: String,
+}
+
+impl Endpoint {
+    /// The env var, else config.json, else Claude Code's own.
+    pub fn load() -> Result<Self> {
+        let config = Config::load()?;
+        let pick = |env: &str, configured: Option<String>, default: &str| {
+            std::env::var(env)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .or(configured)
+                .unwrap_or_else(|| default.to_string())
+        };
+        Ok(Endpoint {
+            authorize_url: pick(AUTHORIZE_URL_ENV, config.oauth_authorize_url, DEFAULT_AUTHORIZE_URL),
+            url: pick(TOKEN_URL_ENV, config.oauth_token_url, DEFAULT_TOKEN_URL),
+            client_id: pick(CLIENT_ID_ENV, config.oauth_client_id, DEFAULT_CLIENT_ID),
+        })
+    }
+}
+
+/// The fields of a token response cswitch uses.
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+    /// Space-separated
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Who logged in; sent with a code exchange
+    #[serde(default)]
+    pub account: Option<TokenAccount>,
+    #[serde(default)]
+    pub organization: Option<TokenOrganization>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenAccount {
+    pub uuid: Option<String>,
+    pub email_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenOrganization {
+    pub uuid: Option<String>,
+    pub name: Option<String>,
+}
+
+impl TokenResponse {
+    /// The login in the shape Claude Code stores it: `claudeAiOauth` JSON,
+    /// with the expiry in ms since the epoch.
+    pub fn credentials_json(&self, now_ms: i64) -> String {
+        let mut oauth = serde_json::Map::new();
+        oauth.insert("accessToken".into(), self.access_token.clone().into());
+        if let Some(refresh_token) = &self.refresh_token {
+            oauth.insert("refreshToken".into(), refresh_token.clone().into());
+        }
+        let expires_in = self.expires_in.unwrap_or(3600);
+        oauth.insert("expiresAt".into(), (now_ms + expires_in * 1000).into());
+        if let Some(scope) = &self.scope {
+            let scopes: Vec<Value> = scope.split_whitespace().map(|s| s.to_string().into()).collect();
+            oauth.insert("scopes".into(), scopes.into());
+        }
+        serde_json::json!({ "claudeAiOauth": oauth }).to_string()
+    }
+
+    /// The account, shaped like `oauthAccount` in ~/.claude.json.
+    pub fn oauth_account(&self) -> Option<Value> {
+        let mut account = serde_json::Map::new();
+        let mut put = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                account.insert(key.to_string(), value.clone().into());
+            }
+        };
+        if let Some(a) = &self.account {
+            put("accountUuid", &a.uuid);
+            put("emailAddress", &a.email_address);
+        }
+        if let Some(o) = &self.organization {
+            put("organizationUuid", &o.uuid);
+            put("organizationName", &o.name);
+        }
+        (!account.is_empty()).then_some(Value::Object(account))
+    }
+}
+
+/// Trade `refresh_token` for a new access token (and maybe a rotated
+/// refresh token).
+pub fn refresh(refresh_token: &str) -> Result<TokenResponse> {
+    let endpoint = Endpoint::load()?;
+    request(
+        &endpoint,
+        &[
+            ("grant_type", "refresh_token"),
+            ("client_id", &endpoint.client_id),
+            ("refresh_token", refresh_token),
+        ],
+    )
+}
+
+/// Trade the `code` a browser login was redirected back with for tokens.
+/// `verifier` is the PKCE secret whose challenge started the login.
+pub fn exchange_code(
+    endpoint: &Endpoint,
+    code: &str,
+    verifier: &str,
+    redirect_uri: &str,
+    state: &str,
+) -> Result<TokenResponse> {
+    request(
+        endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &endpoint.client_id),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", verifier),
+            ("state", state),
+        ],
+    )
+}
+
+/// POST `form` to the endpoint, retrying with backoff on network errors,
+/// 5xx and 429, or after the `Retry-After` the server asks for. Other
+/// statuses are final: a rejected grant will not succeed later.
+fn request(endpoint: &Endpoint, form: &[(&str, &str)]) -> Result<TokenResponse> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .http_status_as_error(false)
+        .user_agent(concat!("cswitch/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into();
+
+    let mut backoff = FIRST_BACKOFF;
+    for attempt in 1..=ATTEMPTS {
+        let (failure, wait) = match agent.post(&endpoint.url).send_form(form.iter().copied()) {
+            Ok(mut response) => {
+                let status = response.status().as_u16();
+                let wait = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| retry_after(v, Utc::now()));
+                let body = response.body_mut().read_to_string().unwrap_or_default();
+                if (200..300).contains(&status) {
+                    return parse_response(&body);
+                }
+                let error = describe_error(status, &body);
+                match classify(status, &body) {
+                    Outcome::Retry => (error, wait),
+                    Outcome::Rejected => return Err(CswitchError::OAuthRejected(error)),
+                    Outcome::Failed => {
+                        return Err(CswitchError::OAuth(format!("Token request failed: {error}")))
+                    }
+                }
+            }
+            Err(e) => (format!("could not reach {}: {e}", endpoint.url), None),
+        };
+        let wait = wait.unwrap_or(backoff);
+        if wait > MAX_RETRY_AFTER {
+            return Err(CswitchError::OAuth(format!(
+                "Token request failed: {failure}; the server asks to wait {}s before retrying",
+                wait.as_secs()
+            )));
+        }
+        if attempt == ATTEMPTS {
+            return Err(CswitchError::OAuth(format!(
+                "Token request failed after {ATTEMPTS} attempts: {failure}"
+            )));
+        }
+        eprintln!("  Token request failed ({failure}), retrying…");
+        std::thread::sleep(wait);
+        backoff *= 2;
+    }
+    unreachable!("the last attempt returns")
+}
+
+fn parse_response(body: &str) -> Result<TokenResponse> {
+    serde_json::from_str(body).map_err(|e| CswitchError::OAuth(format!("Invalid token response: {e}")))
+}
+
+/// What to do about a response that isn't a success.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// A server error or rate limiting: try again
+    Retry,
+    /// The grant or client was refused: log in again
+    Rejected,
+    /// Anything else: the same request will fail again
+    Failed,
+}
+
+fn classify(status: u16, body: &str) -> Outcome {
+    if status >= 500 || status == 429 {
+        return Outcome::Retry;
+    }
+    let json: Value = serde_json::from_str(body).unwrap_or_default();
+    match json["error"].as_str() {
+        Some(code) if REJECTIONS.contains(&code) => Outcome::Rejected,
+        _ => Outcome::Failed,
+    }
+}
+
+/// How long a `Retry-After` header asks to wait: delay-seconds or an HTTP
+/// date.
+fn retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// An error message from a failed response: the OAuth `error` and
+/// `error_description` (RFC 6749), Anthropic's `{"error": {"message"}}`, or
+/// the status alone.
+fn describe_error(status: u16, body: &str) -> String {
+    let json: Value = serde_json::from_str(body).unwrap_or_default();
+    let error = &json["error"];
+    let detail = match error {
+        Value::String(code) => Some(match json["error_description"].as_str() {
+            Some(description) => format!("{code}: {description}"),
+            None => code.clone(),
+        }),
+        Value::Object(obj) => obj
+            .get("message")
+            .or_else(|| obj.get("type"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        _ => None,
+    };
+    match detail {
+        Some(detail) => format!("HTTP {status}, {detail}"),
+        None => format!("HTTP {status}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// A token endpoint on a loopback port answering one request per
+    /// canned response, in order. Joining it returns the request bodies.
+    fn mock(responses: &[&str]) -> (Endpoint, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/oauth/token", listener.local_addr().unwrap());
+        let responses: Vec<String> = responses.iter().map(|r| r.to_string()).collect();
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            bodies
+        });
+        let endpoint = Endpoint {
+            authorize_url: String::new(),
+            url,
+            client_id: "client".into(),
+        };
+        (endpoint, server)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+            body.len()
+        )
+    }
+
+    const TOKENS: &str = r#"{"access_token":"at","refresh_token":"rt","expires_in":60,"scope":"user:inference user:profile"}"#;
+
+    #[test]
+    fn classifies_failed_responses() {
+        assert_eq!(classify(500, ""), Outcome::Retry);
+        assert_eq!(classify(503, "<html>"), Outcome::Retry);
+        assert_eq!(classify(429, r#"{"error":"rate_limited"}"#), Outcome::Retry);
+        assert_eq!(classify(400, r#"{"error":"invalid_grant"}"#), Outcome::Rejected);
+        assert_eq!(classify(401, r#"{"error":"invalid_client"}"#), Outcome::Rejected);
+        assert_eq!(classify(400, r#"{"error":"invalid_request"}"#), Outcome::Failed);
+        assert_eq!(classify(403, r#"{"error":{"message":"forbidden"}}"#), Outcome::Failed);
+        assert_eq!(classify(404, ""), Outcome::Failed);
+    }
+
+    #[test]
+    fn reads_retry_after() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2026 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(retry_after("7", now), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after(" 0 ", now), Some(Duration::ZERO));
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2026 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(retry_after("Wed, 21 Oct 2026 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn describes_errors() {
+        assert_eq!(
+            describe_error(400, r#"{"error":"invalid_grant","error_description":"expired"}"#),
+            "HTTP 400, invalid_grant: expired"
+        );
+        assert_eq!(
+            describe_error(403, r#"{"error":{"type":"permission_error","message":"no"}}"#),
+            "HTTP 403, no"
+        );
+        assert_eq!(describe_error(502, "Bad Gateway"), "HTTP 502");
+    }
+
+    #[test]
+    fn parses_token_response() {
+        let response = parse_response(
+            r#"{"access_token":"at","refresh_token":"rt","expires_in":60,"scope":"a b",
+                "account":{"uuid":"u1","email_address":"me@example.com"},
+                "organization":{"uuid":"o1","name":"Acme"},"token_type":"Bearer"}"#,
+        )
+        .unwrap();
+
+        let creds: Value = serde_json::from_str(&response.credentials_json(1_000)).unwrap();
+        assert_eq!(
+            creds,
+            serde_json::json!({"claudeAiOauth": {
+                "accessToken": "at",
+                "refreshToken": "rt",
+                "expiresAt": 61_000,
+                "scopes": ["a", "b"],
+            }})
+        );
+        assert_eq!(
+            response.oauth_account(),
+            Some(serde_json::json!({
+                "accountUuid": "u1",
+                "emailAddress": "me@example.com",
+                "organizationUuid": "o1",
+                "organizationName": "Acme",
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_minimal_token_response() {
+        let response = parse_response(r#"{"access_token":"at"}"#).unwrap();
+        let creds: Value = serde_json::from_str(&response.credentials_json(0)).unwrap();
+        assert_eq!(
+            creds,
+            serde_json::json!({"claudeAiOauth": {"accessToken": "at", "expiresAt": 3_600_000}})
+        );
+        assert_eq!(response.oauth_account(), None);
+        assert!(parse_response(r#"{"token":"at"}"#).is_err());
+    }
+
+    #[test]
+    fn posts_the_form() {
+        let (endpoint, server) = mock(&[&response("200 OK", "", TOKENS)]);
+        let response = request(&endpoint, &[("grant_type", "refresh_token"), ("refresh_token", "r t")]).unwrap();
+        assert_eq!(response.access_token, "at");
+        assert_eq!(server.join().unwrap(), ["grant_type=refresh_token&refresh_token=r+t"]);
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (endpoint, server) = mock(&[
+            &response("503 Service Unavailable", "Retry-After: 0\r\n", ""),
+            &response("500 Internal Server Error", "Retry-After: 0\r\n", ""),
+            &response("200 OK", "", TOKENS),
+        ]);
+        assert_eq!(request(&endpoint, &[]).unwrap().access_token, "at");
+        assert_eq!(server.join().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn retries_rate_limiting() {
+        let (endpoint, server) = mock(&[
+            &response("429 Too Many Requests", "Retry-After: 0\r\n", r#"{"error":"rate_limited"}"#),
+            &response("200 OK", "", TOKENS),
+        ]);
+        assert_eq!(request(&endpoint, &[]).unwrap().access_token, "at");
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let busy = response("503 Service Unavailable", "Retry-After: 0\r\n", "");
+        let (endpoint, server) = mock(&[&busy, &busy, &busy]);
+        let err = request(&endpoint, &[]).unwrap_err();
+        assert!(matches!(err, CswitchError::OAuth(ref msg) if msg.contains("after 3 attempts")), "{err}");
+        assert_eq!(server.join().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn does_not_wait_out_a_long_retry_after() {
+        let (endpoint, server) = mock(&[&response("429 Too Many Requests", "Retry-After: 3600\r\n", "")]);
+        let err = request(&endpoint, &[]).unwrap_err();
+        assert!(matches!(err, CswitchError::OAuth(ref msg) if msg.contains("3600s")), "{err}");
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn does_not_retry_a_rejected_grant() {
+        let (endpoint, server) = mock(&[&response(
+            "400 Bad Request",
+            "",
+            r#"{"error":"invalid_grant","error_description":"Refresh token expired"}"#,
+        )]);
+        let err = request(&endpoint, &[]).unwrap_err();
+        assert!(matches!(err, CswitchError::OAuthRejected(ref msg) if msg.contains("invalid_grant")), "{err}");
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn does_not_retry_other_client_errors() {
+        let (endpoint, server) = mock(&[&response("400 Bad Request", "", r#"{"error":"invalid_request"}"#)]);
+        let err = request(&endpoint, &[]).unwrap_err();
+        assert!(matches!(err, CswitchError::OAuth(_)), "{err}");
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}